@@ -0,0 +1,5161 @@
+// The packing core: patches, the stages which lay them out, and the exporters which describe the
+// result. Nothing here depends on macroquad, so packing works without a window or a GPU.
+
+use glam::Vec2;
+use quad_rand as rand;
+use rand::ChooseRandom;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+pub mod json;
+#[macro_use]
+pub mod log;
+
+/////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Copy, Clone, Debug)]
+pub struct Patch {
+    pub id: i32,
+    pub center: Vec2,
+    pub extent: Vec2,
+    pub rotation: f32,
+    // the patch's size and rotation as created, before any stage uprighted it; stages must
+    // carry these through unchanged so exporters can report the source image's own size
+    pub source_extent: Vec2,
+    pub source_rotation: f32,
+    // where the patch's top-left sits within its source image once transparent borders are
+    // trimmed away; zero for untrimmed patches
+    pub source_offset: Vec2,
+    // patches sharing a group are packed side by side as one strip, e.g. an animation's frames
+    pub group: Option<u32>,
+}
+
+impl Patch {
+    // a non-negative id derived from a source file's name alone (32 bit FNV-1a), so the same
+    // file gets the same id whichever order it's found in
+    pub fn stable_id_from_name(name: &str) -> i32 {
+        let hash = name.bytes().fold(0x811c_9dc5u32, |hash, byte| {
+            (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+        });
+        (hash & i32::MAX as u32) as i32
+    }
+
+    pub fn width(&self) -> f32 {
+        self.footprint().x
+    }
+
+    pub fn height(&self) -> f32 {
+        self.footprint().y
+    }
+
+    pub fn left(&self) -> f32 {
+        self.center.x - self.width() / 2.
+    }
+
+    pub fn right(&self) -> f32 {
+        self.center.x + self.width() / 2.
+    }
+
+    pub fn top(&self) -> f32 {
+        self.center.y - self.height() / 2.
+    }
+
+    pub fn bottom(&self) -> f32 {
+        self.center.y + self.height() / 2.
+    }
+
+    // extent already reflects any quarter turns; whatever rotation remains beyond the nearest
+    // quarter turn tilts the patch, and its footprint grows to the tilted rect's bounding box
+    pub fn footprint(&self) -> Vec2 {
+        let tilt = self.tilt();
+        if tilt == 0. {
+            self.extent
+        } else {
+            let (sin, cos) = tilt.sin_cos();
+            let (sin, cos) = (sin.abs(), cos.abs());
+            Vec2::new(
+                self.extent.x * cos + self.extent.y * sin,
+                self.extent.x * sin + self.extent.y * cos,
+            )
+        }
+    }
+
+    // the rotation beyond the nearest quarter turn, or zero for an axis-aligned patch
+    pub fn tilt(&self) -> f32 {
+        let residual =
+            self.rotation - quarter_turns(self.rotation) as f32 * std::f32::consts::FRAC_PI_2;
+        if residual.abs() < 1e-6 {
+            0.
+        } else {
+            residual
+        }
+    }
+
+    // the corners of the patch as drawn, which for a tilted patch lie inside its footprint
+    #[cfg(feature = "free-rotation")]
+    pub fn corners(&self) -> [Vec2; 4] {
+        rotated_corners(self.center, self.unrotated_extent(), self.rotation)
+    }
+
+    // the size of the patch before `rotation` is applied, undoing any quarter turns in extent
+    pub fn unrotated_extent(&self) -> Vec2 {
+        if quarter_turns(self.rotation) % 2 != 0 {
+            Vec2::new(self.extent.y, self.extent.x)
+        } else {
+            self.extent
+        }
+    }
+
+    // grows (or with a negative amount, shrinks) the patch by `amount` on every side
+    pub fn extruded(&self, amount: f32) -> Self {
+        Self {
+            extent: self.extent + Vec2::splat(amount * 2.),
+            ..*self
+        }
+    }
+
+    // turns the patch a quarter turn if `config.orientation` calls for it
+    pub fn uprighted(&self, config: &PackingConfig) -> Self {
+        let available = config.width - config.padding * 2.;
+        let turn = match config.orientation {
+            Orientation::AlwaysPortrait => self.width() > self.height(),
+            Orientation::AlwaysLandscape => self.height() > self.width(),
+            Orientation::Never => false,
+            Orientation::Auto => self.width() > available && self.height() <= available,
+        };
+        if turn {
+            Self {
+                extent: Vec2::new(self.extent.y, self.extent.x),
+                rotation: self.rotation + std::f32::consts::FRAC_PI_2,
+                ..*self
+            }
+        } else {
+            *self
+        }
+    }
+
+    pub fn translated(&self, offset: Vec2) -> Self {
+        Self {
+            center: self.center + offset,
+            ..*self
+        }
+    }
+
+    pub fn with_left_and_top(&self, left: f32, top: f32) -> Self {
+        Self {
+            center: Vec2::new(left + self.width() / 2., top + self.height() / 2.),
+            ..*self
+        }
+    }
+
+    // patches which merely share an edge do not intersect
+    pub fn intersects(&self, other: &Patch) -> bool {
+        self.overlaps_by(other, 0.)
+    }
+
+    // whether the patches overlap by more than `epsilon` on both axes; edges closer together
+    // than that count as touching, which absorbs float error in edges meant to be flush
+    pub fn overlaps_by(&self, other: &Patch, epsilon: f32) -> bool {
+        let footprints_intersect = self.left() < other.right() - epsilon
+            && self.right() > other.left() + epsilon
+            && self.top() < other.bottom() - epsilon
+            && self.bottom() > other.top() + epsilon;
+        #[cfg(feature = "free-rotation")]
+        if footprints_intersect && (self.tilt() != 0. || other.tilt() != 0.) {
+            // a tilted patch leaves empty corners in its footprint which a neighbor may use
+            return convex_polygons_intersect(&self.corners(), &other.corners());
+        }
+        footprints_intersect
+    }
+
+    // edges count as inside, like `Page::contains`
+    pub fn contains_point(&self, point: Vec2) -> bool {
+        let in_footprint = point.x >= self.left()
+            && point.x <= self.right()
+            && point.y >= self.top()
+            && point.y <= self.bottom();
+        #[cfg(feature = "free-rotation")]
+        if in_footprint && self.tilt() != 0. {
+            // inside a convex polygon is on the same side of each of its edges
+            let corners = self.corners();
+            let sides: Vec<f32> = (0..corners.len())
+                .map(|i| {
+                    let (a, b) = (corners[i], corners[(i + 1) % corners.len()]);
+                    (b.x - a.x) * (point.y - a.y) - (b.y - a.y) * (point.x - a.x)
+                })
+                .collect();
+            return sides.iter().all(|&side| side >= 0.) || sides.iter().all(|&side| side <= 0.);
+        }
+        in_footprint
+    }
+}
+
+// separating axis test for two convex polygons: they're disjoint exactly when their projections
+// onto some edge normal of either polygon don't overlap; polygons which only touch are disjoint
+#[cfg(feature = "free-rotation")]
+fn convex_polygons_intersect(a: &[Vec2], b: &[Vec2]) -> bool {
+    let project = |polygon: &[Vec2], axis: Vec2| {
+        polygon
+            .iter()
+            .map(|p| p.dot(axis))
+            .fold((f32::MAX, f32::MIN), |(min, max), d| {
+                (min.min(d), max.max(d))
+            })
+    };
+    let separated_along_edge_of = |polygon: &[Vec2]| {
+        (0..polygon.len()).any(|i| {
+            let edge = polygon[(i + 1) % polygon.len()] - polygon[i];
+            let axis = Vec2::new(-edge.y, edge.x);
+            let (a_min, a_max) = project(a, axis);
+            let (b_min, b_max) = project(b, axis);
+            a_max <= b_min || b_max <= a_min
+        })
+    };
+    !separated_along_edge_of(a) && !separated_along_edge_of(b)
+}
+
+fn quarter_turns(rotation: f32) -> i32 {
+    (rotation / std::f32::consts::FRAC_PI_2).round() as i32
+}
+
+/////////////////////////////////////////////////////////////////////////////////
+
+// A step of the packing pipeline: takes the layout produced by the previous stage and returns
+// a new one. Stages are independent of each other so they can be composed in any order.
+pub trait PackStage {
+    fn name(&self) -> &'static str;
+    fn apply(&self, patches: Vec<Patch>, config: PackingConfig) -> Vec<Patch>;
+
+    // the free space a packing stage leaves behind after applying it to `patches`, for
+    // visualizing how it works; stages which don't track free space have none to show
+    fn free_rects(&self, _patches: Vec<Patch>, _config: PackingConfig) -> Vec<Patch> {
+        Vec::new()
+    }
+}
+
+pub struct Pipeline {
+    pub stages: Vec<Box<dyn PackStage>>,
+}
+
+impl Pipeline {
+    // the default pipeline: upright, sort, then pack with the configured algorithm; uniform
+    // tiles only need laying out in a grid
+    pub fn for_config(config: &PackingConfig) -> Pipeline {
+        if let Some(tile) = config.tile {
+            return Pipeline {
+                stages: vec![Box::new(GridTileStage { tile })],
+            };
+        }
+        let mut stages: Vec<Box<dyn PackStage>> = vec![Box::new(UprightStage)];
+        stages.push(Box::new(SortStage { order: config.sort }));
+        match config.packer {
+            Packer::PackedUpwards => {
+                stages.push(Box::new(FlowStage));
+                stages.push(Box::new(PackUpwardsStage {
+                    probe: config.probe_columns,
+                    turn: config.try_turns,
+                }));
+            }
+            Packer::MaxRects => {
+                stages.push(Box::new(FlowStage));
+                stages.push(Box::new(MaxRectsStage));
+            }
+            Packer::Shelf => stages.push(Box::new(ShelfPackStage {
+                policy: ShelfPolicy::NextFit,
+            })),
+            Packer::ShelfFirstFit => stages.push(Box::new(ShelfPackStage {
+                policy: ShelfPolicy::FirstFit,
+            })),
+            Packer::Guillotine => stages.push(Box::new(GuillotineStage)),
+            Packer::Skyline => stages.push(Box::new(SkylineStage)),
+            Packer::DeterministicRows => stages.push(Box::new(DeterministicRowsStage {
+                rows: config.fixed_rows,
+            })),
+        }
+        stages.push(Box::new(SnapStage));
+        Pipeline { stages }
+    }
+
+    // this pipeline without its uprighting stage, for patches whose turns have already been
+    // chosen, as by `with_turns_toggled`
+    pub fn keeping_turns(mut self) -> Pipeline {
+        self.stages
+            .retain(|stage| stage.name() != UprightStage.name());
+        self
+    }
+
+    pub fn run(&self, patches: Vec<Patch>, config: PackingConfig) -> Vec<Patch> {
+        self.stages.iter().fold(patches, |patches, stage| {
+            log_trace!("{}: {} patches", stage.name(), patches.len());
+            apply_stage(stage.as_ref(), patches, config)
+        })
+    }
+
+    // builds a pipeline from a comma separated list of stage names, e.g. "upright,sort-area,shelf"
+    pub fn from_names(names: &str) -> Result<Pipeline, String> {
+        let mut stages: Vec<Box<dyn PackStage>> = Vec::new();
+        for name in names.split(',').map(str::trim) {
+            stages.push(match name {
+                "upright" => Box::new(UprightStage),
+                "sort-height" => Box::new(SortStage {
+                    order: SortOrder::HeightDesc,
+                }),
+                "sort-height-asc" => Box::new(SortStage {
+                    order: SortOrder::HeightAsc,
+                }),
+                "sort-area" => Box::new(SortStage {
+                    order: SortOrder::AreaDesc,
+                }),
+                "sort-area-asc" => Box::new(SortStage {
+                    order: SortOrder::AreaAsc,
+                }),
+                "sort-width" => Box::new(SortStage {
+                    order: SortOrder::WidthDesc,
+                }),
+                "flow" => Box::new(FlowStage),
+                "flow-best-fit" => Box::new(BestFitFlowStage),
+                "pack-upwards" => Box::new(PackUpwardsStage {
+                    probe: false,
+                    turn: false,
+                }),
+                "pack-upwards-probe" => Box::new(PackUpwardsStage {
+                    probe: true,
+                    turn: false,
+                }),
+                "pack-upwards-turn" => Box::new(PackUpwardsStage {
+                    probe: false,
+                    turn: true,
+                }),
+                "compact-left" => Box::new(CompactLeftStage),
+                "maxrects" => Box::new(MaxRectsStage),
+                "shelf" => Box::new(ShelfPackStage {
+                    policy: ShelfPolicy::NextFit,
+                }),
+                "shelf-ffd" => Box::new(ShelfPackStage {
+                    policy: ShelfPolicy::FirstFit,
+                }),
+                "guillotine" => Box::new(GuillotineStage),
+                "skyline" => Box::new(SkylineStage),
+                "rows" => Box::new(DeterministicRowsStage { rows: None }),
+                "snap" => Box::new(SnapStage),
+                _ => return Err(format!("unknown stage \"{}\"", name)),
+            });
+        }
+        Ok(Pipeline { stages })
+    }
+}
+
+// packs `patches` to completion with the default pipeline for `config`
+pub fn pack(config: PackingConfig, patches: Vec<Patch>) -> Vec<Patch> {
+    Pipeline::for_config(&config).run(patches, config)
+}
+
+// `patches` uprighted as `config` would have them, except those in `toggled`, which are turned
+// the other way instead; for trying out a turn the uprighting didn't choose. Pack the result with
+// `Pipeline::keeping_turns` so the choice isn't uprighted away again
+pub fn with_turns_toggled(
+    patches: Vec<Patch>,
+    config: PackingConfig,
+    toggled: &HashSet<i32>,
+) -> Vec<Patch> {
+    // tiling doesn't upright, so neither does this
+    let uprighted = match config.tile {
+        Some(_) => patches,
+        None => apply_stage(&UprightStage, patches, config),
+    };
+    uprighted
+        .iter()
+        .map(|p| match toggled.contains(&p.id) {
+            true => turned_to(p, !is_turned(p)),
+            false => *p,
+        })
+        .collect()
+}
+
+// whether two layouts hold the same patches, in any order
+pub fn same_ids(a: &[Patch], b: &[Patch]) -> bool {
+    let ids = |patches: &[Patch]| {
+        let mut ids: Vec<i32> = patches.iter().map(|p| p.id).collect();
+        ids.sort_unstable();
+        ids
+    };
+    a.len() == b.len() && ids(a) == ids(b)
+}
+
+// stages work on patches grown by the configured extrusion, so extruded edges get their own
+// room and keep `padding` apart; shrinking them back about the same centers leaves each
+// patch's own pixels where the extrusion was placed. grouped patches are packed as one strip
+fn apply_stage(stage: &dyn PackStage, patches: Vec<Patch>, config: PackingConfig) -> Vec<Patch> {
+    let (inner, inset) = inset_by_border(config);
+    let extruded: Vec<Patch> = patches
+        .iter()
+        .map(|p| p.extruded(config.extrude).translated(-inset))
+        .collect();
+    let (strips, groups) = collapse_groups(&extruded);
+    expand_groups(stage.apply(strips, inner), &groups)
+        .iter()
+        .map(|p| p.extruded(-config.extrude).translated(inset))
+        .collect()
+}
+
+// stages only know `padding`, and keep it from the atlas edges as well as between patches. To
+// honor a different border they pack into an atlas grown or shrunk on every side by the
+// difference, returned here with the offset from the inner atlas's origin to the real one's
+fn inset_by_border(config: PackingConfig) -> (PackingConfig, Vec2) {
+    let inset = config.border - config.padding;
+    let inner = PackingConfig {
+        width: config.width - 2. * inset,
+        height: config.height - 2. * inset,
+        border: config.padding,
+        ..config
+    };
+    (inner, Vec2::splat(inset))
+}
+
+// each group's members laid end to end as a single strip, in the order they're met, so stages
+// pack the whole group as one patch; ungrouped patches pass through. returns the members of
+// each group to lay back out along the packed strips
+fn collapse_groups(patches: &[Patch]) -> (Vec<Patch>, HashMap<u32, Vec<Patch>>) {
+    let mut groups: HashMap<u32, Vec<Patch>> = HashMap::new();
+    let mut collapsed = Vec::new();
+    for patch in patches {
+        match patch.group {
+            Some(group) => {
+                let members = groups.entry(group).or_default();
+                // the group's first member holds its place until the strip is built
+                if members.is_empty() {
+                    collapsed.push(*patch);
+                }
+                members.push(*patch);
+            }
+            None => collapsed.push(*patch),
+        }
+    }
+
+    let mut index = 0;
+    for patch in collapsed.iter_mut() {
+        if let Some(group) = patch.group {
+            let members = &groups[&group];
+            // the strip runs along its members' unturned widths, and turns when they're turned
+            let turned = is_turned(&members[0]);
+            let size = members
+                .iter()
+                .map(|m| turned_to(m, false).extent)
+                .fold(Vec2::ZERO, |size, extent| {
+                    Vec2::new(size.x + extent.x, size.y.max(extent.y))
+                });
+            let strip = Patch {
+                // negative like the stages' own scratch patches, and distinct per strip
+                id: -2 - index,
+                center: Vec2::ZERO,
+                extent: size,
+                rotation: 0.,
+                source_extent: size,
+                source_rotation: 0.,
+                source_offset: Vec2::ZERO,
+                group: Some(group),
+            };
+            *patch = turned_to(&strip, turned).with_left_and_top(patch.left(), patch.top());
+            index += 1;
+        }
+    }
+    (collapsed, groups)
+}
+
+// lays each group's members back out end to end along its packed strip, turning them with it
+fn expand_groups(packed: Vec<Patch>, groups: &HashMap<u32, Vec<Patch>>) -> Vec<Patch> {
+    if groups.is_empty() {
+        return packed;
+    }
+    let mut expanded = Vec::new();
+    for strip in packed {
+        let members = match strip.group.and_then(|group| groups.get(&group)) {
+            Some(members) => members,
+            None => {
+                expanded.push(strip);
+                continue;
+            }
+        };
+        let turned = is_turned(&strip);
+        let mut offset = 0.;
+        for member in members {
+            let member = turned_to(member, turned);
+            if turned {
+                expanded.push(member.with_left_and_top(strip.left(), strip.top() + offset));
+                offset += member.height();
+            } else {
+                expanded.push(member.with_left_and_top(strip.left() + offset, strip.top()));
+                offset += member.width();
+            }
+        }
+    }
+    expanded
+}
+
+// whether a patch lies on its side, an odd number of quarter turns from upright; what the
+// exports, stats and viewer all call rotated
+pub fn is_turned(patch: &Patch) -> bool {
+    quarter_turns(patch.rotation) % 2 != 0
+}
+
+// `patch` given a quarter turn either way if needed, so it's turned exactly when `turned` is
+fn turned_to(patch: &Patch, turned: bool) -> Patch {
+    if is_turned(patch) == turned {
+        return *patch;
+    }
+    Patch {
+        extent: Vec2::new(patch.extent.y, patch.extent.x),
+        rotation: patch.rotation
+            + if turned {
+                std::f32::consts::FRAC_PI_2
+            } else {
+                -std::f32::consts::FRAC_PI_2
+            },
+        ..*patch
+    }
+}
+
+// the layout after running the pipeline up to some stage
+pub struct Step {
+    pub name: &'static str,
+    pub patches: Vec<Patch>,
+    // the free space the stage was tracking when it finished, if it tracks any
+    pub free_rects: Vec<Patch>,
+    // how long the stage took to run, zero for the layout a walk starts from
+    pub elapsed: Duration,
+}
+
+// walks a pipeline one stage at a time, keeping each intermediate layout so it can step back
+pub struct PipelineWalk {
+    pub pipeline: Pipeline,
+    pub config: PackingConfig,
+    pub steps: Vec<Step>,
+}
+
+impl PipelineWalk {
+    pub fn new(
+        pipeline: Pipeline,
+        config: PackingConfig,
+        name: &'static str,
+        patches: Vec<Patch>,
+    ) -> Self {
+        Self {
+            pipeline,
+            config,
+            steps: vec![Step {
+                name,
+                patches,
+                free_rects: Vec::new(),
+                elapsed: Duration::ZERO,
+            }],
+        }
+    }
+
+    pub fn current(&self) -> &Step {
+        self.steps.last().unwrap()
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        self.steps.len() > self.pipeline.stages.len()
+    }
+
+    // runs the next stage, returning the layout it started from
+    pub fn advance(&mut self) -> Option<Vec<Patch>> {
+        let stage = self.pipeline.stages.get(self.steps.len() - 1)?;
+        let previous = self.current().patches.clone();
+        let started = Instant::now();
+        let patches = apply_stage(stage.as_ref(), previous.clone(), self.config);
+        let elapsed = started.elapsed();
+        log_trace!(
+            "{}: {} patches in {:.2}ms",
+            stage.name(),
+            patches.len(),
+            elapsed.as_secs_f64() * 1000.
+        );
+        // stages only rearrange patches; transitions pair each patch with its own earlier self
+        debug_assert!(
+            same_ids(&previous, &patches),
+            "{} added or dropped patches",
+            stage.name()
+        );
+        // free space is measured among the extruded patches and strips the stage actually packed
+        let (inner, inset) = inset_by_border(self.config);
+        let extruded: Vec<Patch> = previous
+            .iter()
+            .map(|p| p.extruded(self.config.extrude).translated(-inset))
+            .collect();
+        let free_rects = stage
+            .free_rects(collapse_groups(&extruded).0, inner)
+            .iter()
+            .map(|r| r.translated(inset))
+            .collect();
+        self.steps.push(Step {
+            name: stage.name(),
+            patches,
+            free_rects,
+            elapsed,
+        });
+        Some(previous)
+    }
+
+    // discards the current stage, returning the layout being stepped back from
+    pub fn back(&mut self) -> Option<Vec<Patch>> {
+        if self.steps.len() > 1 {
+            let step = self.steps.pop()?;
+            log_trace!("back from {} to {}", step.name, self.current().name);
+            Some(step.patches)
+        } else {
+            None
+        }
+    }
+
+    // runs or discards stages until `step` is current, where step 0 is the layout the walk
+    // started from and step n follows the nth stage; returns the layout jumped from, or None if
+    // there's no such step or it's current already
+    pub fn jump_to(&mut self, step: usize) -> Option<Vec<Patch>> {
+        if step > self.pipeline.stages.len() || step + 1 == self.steps.len() {
+            return None;
+        }
+        let from = self.current().patches.clone();
+        while self.steps.len() <= step {
+            self.advance();
+        }
+        self.steps.truncate(step + 1);
+        Some(from)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Packer {
+    PackedUpwards,
+    MaxRects,
+    Shelf,
+    ShelfFirstFit,
+    Guillotine,
+    Skyline,
+    DeterministicRows,
+}
+
+impl Packer {
+    pub fn cycled(self) -> Packer {
+        match self {
+            Packer::PackedUpwards => Packer::MaxRects,
+            Packer::MaxRects => Packer::Shelf,
+            Packer::Shelf => Packer::ShelfFirstFit,
+            Packer::ShelfFirstFit => Packer::Guillotine,
+            Packer::Guillotine => Packer::Skyline,
+            Packer::Skyline => Packer::DeterministicRows,
+            Packer::DeterministicRows => Packer::PackedUpwards,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Packer::PackedUpwards => "PackedUpwards",
+            Packer::MaxRects => "MaxRects",
+            Packer::Shelf => "Shelf",
+            Packer::ShelfFirstFit => "ShelfFirstFit",
+            Packer::Guillotine => "Guillotine",
+            Packer::Skyline => "Skyline",
+            Packer::DeterministicRows => "DeterministicRows",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Packer> {
+        match name {
+            "PackedUpwards" => Some(Packer::PackedUpwards),
+            "MaxRects" => Some(Packer::MaxRects),
+            "Shelf" => Some(Packer::Shelf),
+            "ShelfFirstFit" => Some(Packer::ShelfFirstFit),
+            "Guillotine" => Some(Packer::Guillotine),
+            "Skyline" => Some(Packer::Skyline),
+            "DeterministicRows" => Some(Packer::DeterministicRows),
+            _ => None,
+        }
+    }
+}
+
+// the order patches are fed to a packer in; ties fall back to the other dimension, in the same
+// direction. the descending orders keep their original names, which presets and sessions use
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SortOrder {
+    HeightDesc,
+    HeightAsc,
+    AreaDesc,
+    AreaAsc,
+    WidthDesc,
+}
+
+impl SortOrder {
+    pub fn cycled(self) -> SortOrder {
+        match self {
+            SortOrder::HeightDesc => SortOrder::HeightAsc,
+            SortOrder::HeightAsc => SortOrder::AreaDesc,
+            SortOrder::AreaDesc => SortOrder::AreaAsc,
+            SortOrder::AreaAsc => SortOrder::WidthDesc,
+            SortOrder::WidthDesc => SortOrder::HeightDesc,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SortOrder::HeightDesc => "Height",
+            SortOrder::HeightAsc => "Height Ascending",
+            SortOrder::AreaDesc => "Area",
+            SortOrder::AreaAsc => "Area Ascending",
+            SortOrder::WidthDesc => "Width",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<SortOrder> {
+        match name {
+            "Height" => Some(SortOrder::HeightDesc),
+            "Height Ascending" => Some(SortOrder::HeightAsc),
+            "Area" => Some(SortOrder::AreaDesc),
+            "Area Ascending" => Some(SortOrder::AreaAsc),
+            "Width" => Some(SortOrder::WidthDesc),
+            _ => None,
+        }
+    }
+
+    fn compare(self, a: &Patch, b: &Patch) -> Ordering {
+        let area = |p: &Patch| p.width() * p.height();
+        match self {
+            SortOrder::HeightDesc => {
+                larger_first(a.height(), b.height()).then(larger_first(a.width(), b.width()))
+            }
+            SortOrder::HeightAsc => {
+                smaller_first(a.height(), b.height()).then(smaller_first(a.width(), b.width()))
+            }
+            SortOrder::AreaDesc => by_area_then_width(a, b),
+            SortOrder::AreaAsc => {
+                smaller_first(area(a), area(b)).then(smaller_first(a.width(), b.width()))
+            }
+            SortOrder::WidthDesc => {
+                larger_first(a.width(), b.width()).then(larger_first(a.height(), b.height()))
+            }
+        }
+    }
+}
+
+pub const DEFAULT_OVERLAP_EPSILON: f32 = 1e-3;
+
+#[derive(Clone, Copy)]
+pub struct PackingConfig {
+    pub width: f32,
+    pub height: f32,
+    // gap between patches; zero packs patches flush, negative values aren't meaningful and are
+    // clamped to zero when parsing options
+    pub padding: f32,
+    // gap between patches and the atlas edges, usually the same as `padding` but some engines
+    // want a different outer margin than the spacing between sprites
+    pub border: f32,
+    pub packer: Packer,
+    pub sort: SortOrder,
+    pub orientation: Orientation,
+    // pixels each patch's edges are extruded outward to stop bilinear sampling bleeding in from
+    // neighbors; packing keeps the extruded edges `padding` apart, exports report the patch alone
+    pub extrude: f32,
+    // let the packed-upwards packer slide patches sideways into a lower column
+    pub probe_columns: bool,
+    // let the packed-upwards packer turn patches a quarter turn when that leaves them higher
+    pub try_turns: bool,
+    // how far patches must overlap before they count as overlapping, so edges left a hair apart
+    // by float rounding still count as touching; see `DEFAULT_OVERLAP_EPSILON`
+    pub overlap_epsilon: f32,
+    // when set, every patch is forced to this size and laid out in a plain grid of tiles
+    // instead of being sorted and packed
+    pub tile: Option<Vec2>,
+    // the rows the deterministic rows packer lays patches into; None lets it open rows as needed,
+    // each as tall as its tallest patch
+    pub fixed_rows: Option<FixedRows>,
+}
+
+// which way the upright stage turns patches; engines which can't un-rotate frames need `Never`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Orientation {
+    AlwaysPortrait,
+    AlwaysLandscape,
+    Never,
+    // only turn patches too wide for the atlas which fit once turned
+    Auto,
+}
+
+impl Orientation {
+    pub fn name(self) -> &'static str {
+        match self {
+            Orientation::AlwaysPortrait => "portrait",
+            Orientation::AlwaysLandscape => "landscape",
+            Orientation::Never => "never",
+            Orientation::Auto => "auto",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Orientation> {
+        match name {
+            "portrait" => Some(Orientation::AlwaysPortrait),
+            "landscape" => Some(Orientation::AlwaysLandscape),
+            "never" => Some(Orientation::Never),
+            "auto" => Some(Orientation::Auto),
+            _ => None,
+        }
+    }
+}
+
+// what to do with patches too big to fit in the atlas in either orientation
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OversizedPolicy {
+    Scale,
+    Skip,
+    Error,
+}
+
+impl OversizedPolicy {
+    pub fn from_name(name: &str) -> Option<OversizedPolicy> {
+        match name {
+            "scale" => Some(OversizedPolicy::Scale),
+            "skip" => Some(OversizedPolicy::Skip),
+            "error" => Some(OversizedPolicy::Error),
+            _ => None,
+        }
+    }
+}
+
+// crafted worst cases for benchmarking packers, in place of the random grid
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StressPattern {
+    // every patch the same square
+    Squares,
+    // a few patches a fraction of the atlas across among many tiny ones
+    Mixed,
+    // sides stepping through the powers of two, in every pairing
+    PowersOfTwo,
+}
+
+impl StressPattern {
+    pub fn from_name(name: &str) -> Option<StressPattern> {
+        match name {
+            "squares" => Some(StressPattern::Squares),
+            "mixed" => Some(StressPattern::Mixed),
+            "pot" => Some(StressPattern::PowersOfTwo),
+            _ => None,
+        }
+    }
+
+    // the size of each of `count` patches for an atlas of `config`'s size
+    fn extents(self, config: &PackingConfig, count: usize) -> Vec<Vec2> {
+        match self {
+            StressPattern::Squares => {
+                // together they'd cover about half the atlas
+                let side = (config.width * config.height * 0.5 / count.max(1) as f32)
+                    .sqrt()
+                    .floor()
+                    .max(1.);
+                vec![Vec2::splat(side); count]
+            }
+            StressPattern::Mixed => {
+                let huge = (count / 20).max(1);
+                (0..count)
+                    .map(|i| {
+                        if i < huge {
+                            Vec2::new(config.width / 3., config.height / 4.).floor()
+                        } else {
+                            Vec2::new(4. + (i % 5) as f32 * 2., 4. + (i % 3) as f32 * 2.)
+                        }
+                    })
+                    .collect()
+            }
+            StressPattern::PowersOfTwo => {
+                // from 2 up to the largest power of two within a quarter of the atlas
+                let largest = (config.width.min(config.height) / 4.)
+                    .max(2.)
+                    .log2()
+                    .floor();
+                let levels = largest as usize;
+                let side = |level: usize| 2f32.powi(1 + (level % levels) as i32);
+                (0..count)
+                    .map(|i| Vec2::new(side(i), side(i + i / levels)))
+                    .collect()
+            }
+        }
+    }
+}
+
+// `size` rounded up to a whole multiple of `align` each way. block compressed textures (DXT/BC)
+// are stored in 4x4 blocks, so sprites aligned to them never share a block with a neighbor
+pub fn aligned_size(size: Vec2, align: u32) -> Vec2 {
+    if align <= 1 {
+        return size;
+    }
+    let align = align as f32;
+    Vec2::new(
+        (size.x / align).ceil() * align,
+        (size.y / align).ceil() * align,
+    )
+}
+
+#[derive(Clone)]
+pub struct InitialState {
+    pub patches: Vec<Patch>,
+    // source file names keyed by patch id; empty for randomly generated patches
+    pub names: HashMap<i32, String>,
+    // where each loaded image is opaque, keyed by patch id; empty for randomly generated patches
+    #[cfg(feature = "alpha-mask")]
+    pub masks: HashMap<i32, OccupancyMask>,
+}
+
+impl InitialState {
+    // lays out `cols` x `rows` randomly sized patches on a grid; with `shuffle` they're handed on
+    // in a random order rather than row by row, so the pipeline isn't fed grid-ordered input
+    pub fn new(config: PackingConfig, cols: i32, rows: i32, shuffle: bool) -> InitialState {
+        let mut patches: Vec<Patch> = Vec::new();
+        let cell_width = config.width / (cols as f32);
+        let cell_height = config.height / (rows as f32);
+        let max_width = cell_width * 1.1;
+        let max_height = cell_height * 1.1;
+        let min_width = cell_width * 0.5;
+        let min_height = cell_height * 0.5;
+
+        for row in 0..rows {
+            for col in 0..cols {
+                let across_x = (col as f32) / (cols as f32);
+                let across_y = (row as f32) / (rows as f32);
+                // whole pixel sizes, like the sprites they stand in for
+                let width = rand::gen_range(min_width, max_width).round();
+                let height = rand::gen_range(min_height, max_height).round();
+                let center_x = (config.width * across_x) + (cell_width / 2.);
+                let center_y = (config.height * across_y) + (cell_height / 2.);
+                let patch = Patch {
+                    id: patches.len() as i32,
+                    center: Vec2::new(center_x, center_y),
+                    extent: Vec2::new(width, height),
+                    rotation: 0.,
+                    source_extent: Vec2::new(width, height),
+                    source_rotation: 0.,
+                    source_offset: Vec2::ZERO,
+                    group: None,
+                };
+                patches.push(patch);
+            }
+        }
+        if shuffle {
+            patches.shuffle();
+        }
+
+        InitialState {
+            patches,
+            names: HashMap::new(),
+            #[cfg(feature = "alpha-mask")]
+            masks: HashMap::new(),
+        }
+    }
+
+    // exactly `count` randomly sized patches scattered at random over the atlas, sized like those
+    // `new` makes for a grid of about `count` cells
+    pub fn random(config: PackingConfig, count: usize) -> InitialState {
+        let cols = (count as f32).sqrt().ceil().max(1.);
+        let rows = (count as f32 / cols).ceil().max(1.);
+        let cell = Vec2::new(config.width / cols, config.height / rows);
+        let patches = (0..count)
+            .map(|id| {
+                let extent = Vec2::new(
+                    rand::gen_range(cell.x * 0.5, cell.x * 1.1),
+                    rand::gen_range(cell.y * 0.5, cell.y * 1.1),
+                );
+                let center = Vec2::new(
+                    rand::gen_range(
+                        extent.x / 2.,
+                        (config.width - extent.x / 2.).max(extent.x / 2.),
+                    ),
+                    rand::gen_range(
+                        extent.y / 2.,
+                        (config.height - extent.y / 2.).max(extent.y / 2.),
+                    ),
+                );
+                Patch {
+                    id: id as i32,
+                    center,
+                    extent,
+                    rotation: 0.,
+                    source_extent: extent,
+                    source_rotation: 0.,
+                    source_offset: Vec2::ZERO,
+                    group: None,
+                }
+            })
+            .collect();
+
+        InitialState {
+            patches,
+            names: HashMap::new(),
+            #[cfg(feature = "alpha-mask")]
+            masks: HashMap::new(),
+        }
+    }
+
+    // `count` patches shaped by `pattern`, laid out on a roughly square grid; unlike `new`, the
+    // same arguments always give the same patches
+    pub fn stress(config: PackingConfig, pattern: StressPattern, count: usize) -> InitialState {
+        let cols = (count as f32).sqrt().ceil().max(1.);
+        let rows = (count as f32 / cols).ceil().max(1.);
+        let cell = Vec2::new(config.width / cols, config.height / rows);
+        let patches = pattern
+            .extents(&config, count)
+            .into_iter()
+            .enumerate()
+            .map(|(i, extent)| {
+                let col = (i as f32) % cols;
+                let row = ((i as f32) / cols).floor();
+                Patch {
+                    id: i as i32,
+                    center: Vec2::new(col + 0.5, row + 0.5) * cell,
+                    extent,
+                    rotation: 0.,
+                    source_extent: extent,
+                    source_rotation: 0.,
+                    source_offset: Vec2::ZERO,
+                    group: None,
+                }
+            })
+            .collect();
+
+        InitialState {
+            patches,
+            names: HashMap::new(),
+            #[cfg(feature = "alpha-mask")]
+            masks: HashMap::new(),
+        }
+    }
+
+    // patches sized from a manifest listing each sprite's name, width and height, for packing
+    // sprites whose pixels aren't at hand. a ".json" manifest is an array of objects with
+    // "name", "width" and "height"; anything else is read as CSV lines of name,width,height, with
+    // an optional header. patches are laid out on a grid like loaded images, with ids from names
+    pub fn from_manifest(config: PackingConfig, path: &Path) -> std::io::Result<InitialState> {
+        let invalid =
+            |message: String| std::io::Error::new(std::io::ErrorKind::InvalidData, message);
+        let text = std::fs::read_to_string(path)?;
+        let is_json = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("json"));
+
+        let mut entries: Vec<(String, Vec2)> = Vec::new();
+        if is_json {
+            let document = json::parse(&text).map_err(invalid)?;
+            let sprites = document
+                .as_array()
+                .ok_or_else(|| invalid("expected an array of sprites".to_string()))?;
+            for (i, sprite) in sprites.iter().enumerate() {
+                let name = sprite.get("name").and_then(json::Value::as_str);
+                let width = sprite.get("width").and_then(json::Value::as_f32);
+                let height = sprite.get("height").and_then(json::Value::as_f32);
+                match (name, width, height) {
+                    (Some(name), Some(width), Some(height)) => {
+                        entries.push((name.to_string(), Vec2::new(width, height)))
+                    }
+                    _ => {
+                        return Err(invalid(format!(
+                            "sprite {} needs a \"name\", \"width\" and \"height\"",
+                            i
+                        )))
+                    }
+                }
+            }
+        } else {
+            for (i, line) in text.lines().enumerate() {
+                let line = line.trim();
+                if line.is_empty() || (i == 0 && line.eq_ignore_ascii_case("name,width,height")) {
+                    continue;
+                }
+                // split from the right, so names holding commas needn't be quoted
+                let mut fields = line.rsplitn(3, ',');
+                let height = fields.next().and_then(|f| f.trim().parse::<f32>().ok());
+                let width = fields.next().and_then(|f| f.trim().parse::<f32>().ok());
+                let name = fields.next().map(|name| {
+                    let name = name.trim();
+                    match name.strip_prefix('"').and_then(|n| n.strip_suffix('"')) {
+                        Some(quoted) => quoted.replace("\"\"", "\""),
+                        None => name.to_string(),
+                    }
+                });
+                match (name, width, height) {
+                    (Some(name), Some(width), Some(height)) => {
+                        entries.push((name, Vec2::new(width, height)))
+                    }
+                    _ => {
+                        return Err(invalid(format!(
+                            "line {} should be name,width,height",
+                            i + 1
+                        )))
+                    }
+                }
+            }
+        }
+        if let Some((name, _)) = entries.iter().find(|(_, size)| size.min_element() <= 0.) {
+            return Err(invalid(format!(
+                "{} needs a positive width and height",
+                name
+            )));
+        }
+
+        let cols = (entries.len() as f32).sqrt().ceil().max(1.);
+        let rows = (entries.len() as f32 / cols).ceil().max(1.);
+        let cell = Vec2::new(config.width / cols, config.height / rows);
+        let mut names = HashMap::new();
+        let mut patches = Vec::with_capacity(entries.len());
+        for (i, (name, size)) in entries.into_iter().enumerate() {
+            // as with loaded images, a colliding name takes the next free id
+            let mut id = Patch::stable_id_from_name(&name);
+            while names.contains_key(&id) {
+                id = id.wrapping_add(1) & i32::MAX;
+            }
+            let (col, row) = ((i as f32) % cols, (i as f32 / cols).floor());
+            patches.push(Patch {
+                id,
+                center: Vec2::new(cell.x * (col + 0.5), cell.y * (row + 0.5)),
+                extent: size,
+                rotation: 0.,
+                source_extent: size,
+                source_rotation: 0.,
+                source_offset: Vec2::ZERO,
+                group: None,
+            });
+            names.insert(id, name);
+        }
+
+        Ok(InitialState {
+            patches,
+            names,
+            #[cfg(feature = "alpha-mask")]
+            masks: HashMap::new(),
+        })
+    }
+
+    // turns each patch by a random angle of up to `max_degrees` either way
+    #[cfg(feature = "free-rotation")]
+    pub fn tilt(&mut self, max_degrees: f32) {
+        let max = max_degrees.to_radians();
+        for patch in &mut self.patches {
+            let tilt = rand::gen_range(-max, max);
+            patch.rotation += tilt;
+            patch.source_rotation += tilt;
+        }
+    }
+
+    // grows each patch to the next multiple of `align` each way, leaving its source size and
+    // offset as they were; the extra room is transparent, to the right of and below the image
+    pub fn align(&mut self, align: u32) {
+        for patch in &mut self.patches {
+            patch.extent = aligned_size(patch.extent, align);
+        }
+    }
+
+    // groups loaded images named alike but for a trailing frame number, like "run_01.png" and
+    // "run_02.png", so each animation packs as one strip
+    pub fn group_frames(&mut self) {
+        fn stem(name: &str) -> Option<&str> {
+            let base = name.rsplit_once('.').map_or(name, |(base, _)| base);
+            let unnumbered = base.trim_end_matches(|c: char| c.is_ascii_digit());
+            if unnumbered.len() == base.len() {
+                None
+            } else {
+                Some(unnumbered.trim_end_matches(['_', '-', ' ']))
+            }
+        }
+        let stems: HashMap<i32, &str> = self
+            .names
+            .iter()
+            .filter_map(|(&id, name)| stem(name).map(|stem| (id, stem)))
+            .collect();
+        let mut frames: HashMap<&str, usize> = HashMap::new();
+        for stem in stems.values() {
+            *frames.entry(stem).or_default() += 1;
+        }
+        for patch in &mut self.patches {
+            // a lone numbered image has nothing to be adjacent to
+            if let Some(stem) = stems.get(&patch.id).filter(|stem| frames[*stem] > 1) {
+                patch.group = Some(Patch::stable_id_from_name(stem) as u32);
+            }
+        }
+    }
+
+    // applies `policy` to patches which don't fit inside the padded atlas even after uprighting,
+    // returning their ids, or an error listing them under `OversizedPolicy::Error`
+    pub fn fit_oversized(
+        &mut self,
+        config: &PackingConfig,
+        policy: OversizedPolicy,
+    ) -> Result<Vec<i32>, String> {
+        // patches are packed with their extruded edges `border` in from the atlas's edges
+        let available = Vec2::new(
+            config.width - (config.border + config.extrude) * 2.,
+            config.height - (config.border + config.extrude) * 2.,
+        );
+        // the largest uniform scale at which the patch fits in its better orientation
+        let fit_scale = |patch: &Patch| {
+            let size = patch.uprighted(config).footprint();
+            let scale = (available.x / size.x).min(available.y / size.y);
+            if config.orientation == Orientation::Never {
+                scale
+            } else {
+                scale.max((available.x / size.y).min(available.y / size.x))
+            }
+        };
+        let oversized: Vec<i32> = self
+            .patches
+            .iter()
+            .filter(|patch| fit_scale(patch) < 1.)
+            .map(|patch| patch.id)
+            .collect();
+        if oversized.is_empty() {
+            return Ok(oversized);
+        }
+
+        match policy {
+            OversizedPolicy::Scale => {
+                for patch in &mut self.patches {
+                    let scale = fit_scale(patch);
+                    if scale < 1. {
+                        // the source rect shrinks with the frame, so trim offsets still line up
+                        patch.extent *= scale;
+                        patch.source_extent *= scale;
+                        patch.source_offset *= scale;
+                    }
+                }
+            }
+            OversizedPolicy::Skip => self.patches.retain(|patch| !oversized.contains(&patch.id)),
+            OversizedPolicy::Error => {
+                return Err(format!(
+                    "patches {:?} don't fit in the {}x{} atlas",
+                    oversized, config.width, config.height
+                ))
+            }
+        }
+        Ok(oversized)
+    }
+}
+
+pub struct UprightStage;
+
+impl PackStage for UprightStage {
+    fn name(&self) -> &'static str {
+        "Uprighted"
+    }
+
+    fn apply(&self, patches: Vec<Patch>, config: PackingConfig) -> Vec<Patch> {
+        patches.iter().map(|r| r.uprighted(&config)).collect()
+    }
+}
+
+pub struct SortStage {
+    pub order: SortOrder,
+}
+
+impl PackStage for SortStage {
+    fn name(&self) -> &'static str {
+        match self.order {
+            SortOrder::HeightDesc => "Sorted by Height",
+            SortOrder::HeightAsc => "Sorted by Height (ascending)",
+            SortOrder::AreaDesc => "Sorted by Area",
+            SortOrder::AreaAsc => "Sorted by Area (ascending)",
+            SortOrder::WidthDesc => "Sorted by Width",
+        }
+    }
+
+    fn apply(&self, patches: Vec<Patch>, config: PackingConfig) -> Vec<Patch> {
+        let mut sorted = patches;
+        sorted.sort_by(|a, b| self.order.compare(a, b));
+        arranged_in_row(sorted, config.padding)
+    }
+}
+
+// a total, descending order for sort keys: NaN (from a degenerate extent) sorts after every
+// number instead of panicking, and sorts are stable so equal keys keep their input order
+fn larger_first(a: f32, b: f32) -> Ordering {
+    match (a.is_nan(), b.is_nan()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Greater,
+        (false, true) => Ordering::Less,
+        (false, false) => b.partial_cmp(&a).unwrap(),
+    }
+}
+
+// larger_first's ascending counterpart, still sorting NaN last
+fn smaller_first(a: f32, b: f32) -> Ordering {
+    match (a.is_nan(), b.is_nan()) {
+        (false, false) => a.partial_cmp(&b).unwrap(),
+        _ => larger_first(a, b),
+    }
+}
+
+fn by_area_then_width(a: &Patch, b: &Patch) -> Ordering {
+    larger_first(a.width() * a.height(), b.width() * b.height())
+        .then(larger_first(a.width(), b.width()))
+}
+
+// lays sorted patches out left to right in a single row, so the sort order is visible
+fn arranged_in_row(sorted: Vec<Patch>, padding: f32) -> Vec<Patch> {
+    let mut arranged: Vec<Patch> = Vec::new();
+    for patch in sorted {
+        arranged.push(if let Some(last) = arranged.last() {
+            patch.with_left_and_top(last.right() + padding, padding)
+        } else {
+            patch.with_left_and_top(padding, padding)
+        });
+    }
+    arranged
+}
+
+pub struct FlowStage;
+
+impl PackStage for FlowStage {
+    fn name(&self) -> &'static str {
+        "Flowed"
+    }
+
+    fn apply(&self, patches: Vec<Patch>, config: PackingConfig) -> Vec<Patch> {
+        let padding = config.padding;
+        let mut current_y = padding;
+        let mut current_x = padding;
+        let mut row_height = 0f32;
+        let mut result: Vec<Patch> = Vec::new();
+        let mut row = 0;
+
+        for patch in &patches {
+            if row % 2 == 0 {
+                // an overflowing patch starts the next, right-to-left row flush against the
+                // right padding; a row's first patch stays put even if it's too wide
+                if current_x > padding && current_x + patch.width() > config.width - padding {
+                    current_x = config.width - padding - patch.width();
+                    current_y += row_height;
+                    row_height = 0f32;
+                    row += 1;
+                }
+            } else {
+                current_x -= patch.width() + padding;
+                if current_x < padding {
+                    current_x = padding;
+                    current_y += row_height;
+                    row_height = 0.;
+                    row += 1;
+                }
+            }
+
+            result.push(patch.with_left_and_top(current_x, current_y));
+            row_height = row_height.max(patch.height() + padding);
+
+            if row % 2 == 0 {
+                current_x += patch.width() + padding;
+            }
+        }
+
+        result
+    }
+}
+
+// an open row of a best-fit flow
+struct FlowRow {
+    top: f32,
+    height: f32,
+    // width left between the row's last patch and the right padding
+    remaining: f32,
+}
+
+// flows patches left to right like `FlowStage`, but drops each into whichever open row it
+// leaves the least width unused in, so short patches backfill the ends of earlier rows
+pub struct BestFitFlowStage;
+
+impl PackStage for BestFitFlowStage {
+    fn name(&self) -> &'static str {
+        "Flowed (best fit)"
+    }
+
+    fn apply(&self, patches: Vec<Patch>, config: PackingConfig) -> Vec<Patch> {
+        let padding = config.padding;
+        let row_width = config.width - 2. * padding;
+        let mut rows: Vec<FlowRow> = Vec::new();
+        let mut result: Vec<Patch> = Vec::new();
+
+        for patch in &patches {
+            let last = rows.len().checked_sub(1);
+            // only the last row can grow taller without running into the row below it
+            let best = rows
+                .iter()
+                .enumerate()
+                .filter(|&(i, row)| {
+                    patch.width() <= row.remaining
+                        && (Some(i) == last || patch.height() <= row.height)
+                })
+                .min_by(|(_, a), (_, b)| a.remaining.partial_cmp(&b.remaining).unwrap())
+                .map(|(i, _)| i);
+
+            let index = match best {
+                Some(index) => index,
+                None => {
+                    let top = rows
+                        .last()
+                        .map_or(padding, |row| row.top + row.height + padding);
+                    rows.push(FlowRow {
+                        top,
+                        height: 0.,
+                        remaining: row_width,
+                    });
+                    rows.len() - 1
+                }
+            };
+
+            // a row's first patch stays put even if it's too wide
+            let row = &mut rows[index];
+            result.push(patch.with_left_and_top(padding + row_width - row.remaining, row.top));
+            row.height = row.height.max(patch.height());
+            row.remaining -= patch.width() + padding;
+        }
+
+        result
+    }
+}
+
+pub struct PackUpwardsStage {
+    // also try sliding each patch sideways, keeping whichever column lets it rest highest
+    pub probe: bool,
+    // also try each patch turned a quarter turn, keeping whichever way up ends higher; never
+    // turns patches when the orientation is `Never`
+    pub turn: bool,
+}
+
+// evenly spaced columns tried across the atlas when probing, besides the patch's own
+const PACK_UPWARDS_PROBES: usize = 8;
+
+impl PackStage for PackUpwardsStage {
+    fn name(&self) -> &'static str {
+        match (self.probe, self.turn) {
+            (false, false) => "Packed Upwards",
+            (true, false) => "Packed Upwards (probing)",
+            (false, true) => "Packed Upwards (turning)",
+            (true, true) => "Packed Upwards (probing, turning)",
+        }
+    }
+
+    fn apply(&self, patches: Vec<Patch>, config: PackingConfig) -> Vec<Patch> {
+        let single = Self::pack(&patches, config, false, false);
+        let turn = self.turn && config.orientation != Orientation::Never;
+        if !self.probe && !turn {
+            return single;
+        }
+
+        // probing and turning are greedy, so a patch grabbing a low spot can strand later ones
+        // higher up; keep whichever layout ends up shorter so they never cost height
+        let probed = Self::pack(&patches, config, self.probe, turn);
+        let height = |patches: &[Patch]| bounding_box(patches).1.y;
+        if height(&probed) <= height(&single) {
+            probed
+        } else {
+            single
+        }
+    }
+}
+
+impl PackUpwardsStage {
+    fn pack(patches: &[Patch], config: PackingConfig, probe: bool, turn: bool) -> Vec<Patch> {
+        let mut result = patches.to_vec();
+        // cells about one patch wide, so a column query only touches the patches near it
+        let mean_width =
+            patches.iter().map(|p| p.width()).sum::<f32>() / patches.len().max(1) as f32;
+        let mut placed = SpatialGrid::new(mean_width.max(1.) + config.padding);
+
+        // higher patches settle first so each only rises past patches already in their final
+        // spot; flowed rows are in this order already, but a compacted layout may not be
+        for i in settle_order(patches, Patch::top) {
+            let patch = &patches[i];
+            let mut packed = Self::settled(patch, &placed, &config, probe);
+            if turn {
+                // turned about its center, then kept in the atlas if that pushed it past an edge
+                let turned = turned_to(patch, !is_turned(patch));
+                let right_edge = config.width - config.padding;
+                let left = turned
+                    .left()
+                    .min(right_edge - turned.width())
+                    .max(config.padding);
+                if turned.width() <= right_edge - config.padding {
+                    let turned = turned.with_left_and_top(left, turned.top());
+                    let settled = Self::settled(&turned, &placed, &config, probe);
+                    // ties keep the patch the way up it came
+                    if settled.bottom() < packed.bottom() {
+                        packed = settled;
+                    }
+                }
+            }
+            placed.insert(packed);
+            result[i] = packed;
+        }
+
+        result
+    }
+
+    // `patch` raised in its own column or, when probing, whichever column lets it rest highest
+    fn settled(patch: &Patch, placed: &SpatialGrid, config: &PackingConfig, probe: bool) -> Patch {
+        let mut packed = Self::raised(patch, patch.left(), placed, config);
+        if probe {
+            let span = (config.width - 2. * config.padding - patch.width()).max(0.);
+            for i in 0..=PACK_UPWARDS_PROBES {
+                let left = config.padding + span * i as f32 / PACK_UPWARDS_PROBES as f32;
+                let probed = Self::raised(patch, left, placed, config);
+                // ties keep the patch in its own column
+                if probed.top() < packed.top() {
+                    packed = probed;
+                }
+            }
+        }
+        packed
+    }
+
+    // `patch` moved to `left` and pulled up until it rests `padding` below whatever is above it
+    fn raised(patch: &Patch, left: f32, placed: &SpatialGrid, config: &PackingConfig) -> Patch {
+        let (padding, epsilon) = (config.padding, config.overlap_epsilon);
+        // define a rect going from top of this rect to top of screen
+        let test_height = patch.top();
+        let test = Patch {
+            id: -1,
+            center: Vec2::new(left + patch.width() / 2., test_height / 2.),
+            extent: Vec2::new(patch.width(), test_height),
+            rotation: 0.,
+            source_extent: Vec2::new(patch.width(), test_height),
+            source_rotation: 0.,
+            source_offset: Vec2::ZERO,
+            group: None,
+        };
+
+        let mut bottom: f32 = 0.;
+        for candidate in Self::find_intersections(test, placed, epsilon) {
+            bottom = bottom.max(candidate.bottom());
+        }
+
+        // a patch which probed its way below the rows above can sit in the gap the column query
+        // doesn't see, so keep dropping beneath anything the resting spot would overlap; `top` only
+        // ever grows, so rounding in the patch's edges can't leave it bouncing on the same patch
+        let mut top = bottom + padding;
+        loop {
+            let rested = patch.with_left_and_top(left, top);
+            match placed
+                .near(&rested)
+                .find(|p| rested.overlaps_by(p, epsilon) && p.bottom() + padding > top)
+            {
+                Some(below) => top = below.bottom() + padding,
+                None => return rested,
+            }
+        }
+    }
+
+    // a patch resting exactly on test's bottom edge still blocks it, but one merely touching
+    // its sides doesn't, so zero padding packs neighbouring columns flush
+    fn find_intersections(test: Patch, among: &SpatialGrid, epsilon: f32) -> Vec<Patch> {
+        among
+            .near(&test)
+            .filter(|p| Self::blocks(&test, p, epsilon))
+            .copied()
+            .collect()
+    }
+
+    fn blocks(test: &Patch, p: &Patch, epsilon: f32) -> bool {
+        test.left() < p.right() - epsilon
+            && test.right() > p.left() + epsilon
+            && test.top() <= p.bottom()
+            && test.bottom() >= p.top()
+    }
+}
+
+// indices of `patches` ordered by `edge`, ties keeping their order
+fn settle_order(patches: &[Patch], edge: fn(&Patch) -> f32) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..patches.len()).collect();
+    order.sort_by(|&a, &b| {
+        edge(&patches[a])
+            .partial_cmp(&edge(&patches[b]))
+            .unwrap_or(Ordering::Equal)
+    });
+    order
+}
+
+// the sideways mirror of packing upwards: slides each patch left until it rests `padding` right
+// of whatever is beside it. alternating the two tightens a layout further than either alone
+pub struct CompactLeftStage;
+
+impl PackStage for CompactLeftStage {
+    fn name(&self) -> &'static str {
+        "Compacted Left"
+    }
+
+    fn apply(&self, patches: Vec<Patch>, config: PackingConfig) -> Vec<Patch> {
+        let mut result = patches.clone();
+        // cells about one patch tall, so a row query only touches the patches near it
+        let mean_height =
+            patches.iter().map(|p| p.height()).sum::<f32>() / patches.len().max(1) as f32;
+        let mut placed = SpatialGrid::new(mean_height.max(1.) + config.padding);
+
+        for i in settle_order(&patches, Patch::left) {
+            let patch = &patches[i];
+            // define a rect going from the left of this rect to the left of the atlas, grown by
+            // the padding above and below so patches don't slide in closer than it
+            let test_width = patch.left();
+            let test_height = patch.height() + 2. * config.padding;
+            let test = Patch {
+                id: -1,
+                center: Vec2::new(test_width / 2., patch.center.y),
+                extent: Vec2::new(test_width, test_height),
+                rotation: 0.,
+                source_extent: Vec2::new(test_width, test_height),
+                source_rotation: 0.,
+                source_offset: Vec2::ZERO,
+                group: None,
+            };
+
+            let right = placed
+                .near(&test)
+                .filter(|p| Self::blocks(&test, p))
+                .map(Patch::right)
+                .fold(0., f32::max);
+            // a patch already inside the padding stays put rather than being pushed right
+            let left = (right + config.padding).min(patch.left());
+            let packed = patch.with_left_and_top(left, patch.top());
+            placed.insert(packed);
+            result[i] = packed;
+        }
+
+        result
+    }
+}
+
+impl CompactLeftStage {
+    // PackUpwardsStage::blocks on its side: a patch touching test's left edge blocks it, one
+    // merely touching its top or bottom doesn't
+    fn blocks(test: &Patch, p: &Patch) -> bool {
+        test.top() < p.bottom()
+            && test.bottom() > p.top()
+            && test.left() <= p.right()
+            && test.right() >= p.left()
+    }
+}
+
+// buckets patches into uniform square cells by their footprints, so finding the patches near a
+// rect only visits the cells it covers rather than every patch
+struct SpatialGrid {
+    cell_size: f32,
+    patches: Vec<Patch>,
+    // indices into `patches` of every patch whose footprint touches each cell
+    cells: HashMap<(i32, i32), Vec<usize>>,
+}
+
+impl SpatialGrid {
+    fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            patches: Vec::new(),
+            cells: HashMap::new(),
+        }
+    }
+
+    // the cells covering `patch`, edges included, so patches which merely touch share a cell
+    fn cell_range(&self, patch: &Patch) -> impl Iterator<Item = (i32, i32)> {
+        let cell = |v: f32| (v / self.cell_size).floor() as i32;
+        let (left, right) = (cell(patch.left()), cell(patch.right()));
+        let (top, bottom) = (cell(patch.top()), cell(patch.bottom()));
+        (left..=right).flat_map(move |x| (top..=bottom).map(move |y| (x, y)))
+    }
+
+    fn insert(&mut self, patch: Patch) {
+        let index = self.patches.len();
+        self.patches.push(patch);
+        for key in self.cell_range(&patch).collect::<Vec<_>>() {
+            self.cells.entry(key).or_default().push(index);
+        }
+    }
+
+    // every patch sharing a cell with `patch`, each once and in insertion order
+    fn near(&self, patch: &Patch) -> impl Iterator<Item = &Patch> {
+        let mut indices: Vec<usize> = self
+            .cell_range(patch)
+            .filter_map(|key| self.cells.get(&key))
+            .flatten()
+            .copied()
+            .collect();
+        indices.sort_unstable();
+        indices.dedup();
+        indices.into_iter().map(move |i| &self.patches[i])
+    }
+}
+
+pub struct MaxRectsStage;
+
+impl PackStage for MaxRectsStage {
+    fn name(&self) -> &'static str {
+        "MaxRects"
+    }
+
+    fn apply(&self, patches: Vec<Patch>, config: PackingConfig) -> Vec<Patch> {
+        Self::pack(patches, config).0
+    }
+
+    fn free_rects(&self, patches: Vec<Patch>, config: PackingConfig) -> Vec<Patch> {
+        Self::pack(patches, config).1
+    }
+}
+
+impl MaxRectsStage {
+    // returns the packed patches and the free space left over once they're placed
+    fn pack(patches: Vec<Patch>, config: PackingConfig) -> (Vec<Patch>, Vec<Patch>) {
+        let padding = config.padding;
+
+        // each placement reserves its extent plus one padding on the right and bottom, so the
+        // bin starts one padding in from the top-left to keep the same gap at the atlas edges
+        let stacked_height: f32 = patches.iter().map(|p| p.height() + padding).sum();
+        let mut free_rects = vec![Self::free_rect(
+            padding,
+            padding,
+            config.width - padding,
+            (config.height - padding).max(stacked_height),
+        )];
+
+        let mut result = Vec::new();
+        for patch in &patches {
+            let width = patch.width() + padding;
+            let height = patch.height() + padding;
+
+            let best = free_rects
+                .iter()
+                .filter(|free| free.width() >= width && free.height() >= height)
+                .min_by(|a, b| {
+                    let leftover_a = a.width() * a.height() - width * height;
+                    let leftover_b = b.width() * b.height() - width * height;
+                    leftover_a.partial_cmp(&leftover_b).unwrap()
+                })
+                .copied();
+
+            if let Some(best) = best {
+                let placed = Self::free_rect(best.left(), best.top(), width, height);
+                free_rects = Self::split_free_rects(&free_rects, &placed);
+                result.push(patch.with_left_and_top(best.left(), best.top()));
+            } else {
+                // too wide for the atlas; leave it where the previous stage put it
+                result.push(*patch);
+            }
+        }
+
+        (result, free_rects)
+    }
+
+    fn free_rect(left: f32, top: f32, width: f32, height: f32) -> Patch {
+        Patch {
+            id: -1,
+            center: Vec2::new(left + width / 2., top + height / 2.),
+            extent: Vec2::new(width, height),
+            rotation: 0.,
+            source_extent: Vec2::new(width, height),
+            source_rotation: 0.,
+            source_offset: Vec2::ZERO,
+            group: None,
+        }
+    }
+
+    fn contains(outer: &Patch, inner: &Patch) -> bool {
+        inner.left() >= outer.left()
+            && inner.right() <= outer.right()
+            && inner.top() >= outer.top()
+            && inner.bottom() <= outer.bottom()
+    }
+
+    fn split_free_rects(free_rects: &[Patch], placed: &Patch) -> Vec<Patch> {
+        let mut split = Vec::new();
+        for free in free_rects {
+            if !free.intersects(placed) {
+                split.push(*free);
+                continue;
+            }
+
+            if placed.left() > free.left() {
+                split.push(Self::free_rect(
+                    free.left(),
+                    free.top(),
+                    placed.left() - free.left(),
+                    free.height(),
+                ));
+            }
+            if placed.right() < free.right() {
+                split.push(Self::free_rect(
+                    placed.right(),
+                    free.top(),
+                    free.right() - placed.right(),
+                    free.height(),
+                ));
+            }
+            if placed.top() > free.top() {
+                split.push(Self::free_rect(
+                    free.left(),
+                    free.top(),
+                    free.width(),
+                    placed.top() - free.top(),
+                ));
+            }
+            if placed.bottom() < free.bottom() {
+                split.push(Self::free_rect(
+                    free.left(),
+                    placed.bottom(),
+                    free.width(),
+                    free.bottom() - placed.bottom(),
+                ));
+            }
+        }
+
+        // drop any free rect wholly contained by another
+        let mut pruned: Vec<Patch> = Vec::new();
+        for (i, candidate) in split.iter().enumerate() {
+            let redundant = split.iter().enumerate().any(|(j, other)| {
+                i != j
+                    && Self::contains(other, candidate)
+                    && (!Self::contains(candidate, other) || j < i)
+            });
+            if !redundant {
+                pruned.push(*candidate);
+            }
+        }
+        pruned
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ShelfPolicy {
+    // next-fit: only the most recently opened shelf is a candidate
+    NextFit,
+    // first-fit: any open shelf with room is a candidate, so earlier shelves get backfilled
+    FirstFit,
+}
+
+struct Shelf {
+    top: f32,
+    height: f32,
+    next_x: f32,
+}
+
+pub struct ShelfPackStage {
+    pub policy: ShelfPolicy,
+}
+
+impl PackStage for ShelfPackStage {
+    fn name(&self) -> &'static str {
+        match self.policy {
+            ShelfPolicy::NextFit => "Shelf Packed (NFD)",
+            ShelfPolicy::FirstFit => "Shelf Packed (FFD)",
+        }
+    }
+
+    fn apply(&self, patches: Vec<Patch>, config: PackingConfig) -> Vec<Patch> {
+        let padding = config.padding;
+        let mut shelves: Vec<Shelf> = Vec::new();
+        let mut result: Vec<Patch> = Vec::new();
+
+        for patch in &patches {
+            let candidates = match self.policy {
+                ShelfPolicy::NextFit => shelves.len().saturating_sub(1)..shelves.len(),
+                ShelfPolicy::FirstFit => 0..shelves.len(),
+            };
+            let last = shelves.len().checked_sub(1);
+            // an empty shelf takes anything, and only the last shelf can grow taller
+            let fits = |index: usize, shelf: &Shelf| {
+                (shelf.next_x <= padding || shelf.next_x + patch.width() <= config.width - padding)
+                    && (Some(index) == last || patch.height() <= shelf.height)
+            };
+
+            let index = match candidates.into_iter().find(|&i| fits(i, &shelves[i])) {
+                Some(index) => index,
+                None => {
+                    let top = shelves
+                        .last()
+                        .map_or(padding, |shelf| shelf.top + shelf.height + padding);
+                    shelves.push(Shelf {
+                        top,
+                        height: 0.,
+                        next_x: padding,
+                    });
+                    shelves.len() - 1
+                }
+            };
+
+            let shelf = &mut shelves[index];
+            result.push(patch.with_left_and_top(shelf.next_x, shelf.top));
+            shelf.height = shelf.height.max(patch.height());
+            shelf.next_x += patch.width() + padding;
+        }
+
+        result
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FixedRows {
+    // exactly this many rows, each as tall as its tallest patch
+    Count(usize),
+    // rows of this height, opening as many as it takes; a taller patch gets a row its height
+    Height(f32),
+}
+
+// lays patches left to right into rows, each patch in the first row it fits or, given a count
+// of rows, the shortest one, with no gravity or compaction afterwards, so the same input always
+// gives the same plainly ordered layout. rows are stacked once filled, so a row growing taller
+// never disturbs the others
+pub struct DeterministicRowsStage {
+    pub rows: Option<FixedRows>,
+}
+
+struct Row {
+    height: f32,
+    next_x: f32,
+    members: Vec<usize>,
+}
+
+impl PackStage for DeterministicRowsStage {
+    fn name(&self) -> &'static str {
+        "Deterministic Rows"
+    }
+
+    fn apply(&self, patches: Vec<Patch>, config: PackingConfig) -> Vec<Patch> {
+        let padding = config.padding;
+        let mut rows: Vec<Row> = Vec::new();
+        if let Some(FixedRows::Count(count)) = self.rows {
+            rows.extend((0..count.max(1)).map(|_| Row {
+                height: 0.,
+                next_x: padding,
+                members: Vec::new(),
+            }));
+        }
+
+        let mut lefts = Vec::with_capacity(patches.len());
+        for (i, patch) in patches.iter().enumerate() {
+            // an empty row takes anything, and fixed height rows only take patches they're
+            // tall enough for
+            let fits = |row: &Row| {
+                row.members.is_empty()
+                    || (row.next_x + patch.width() <= config.width - padding
+                        && match self.rows {
+                            Some(FixedRows::Height(height)) => patch.height() <= height,
+                            _ => true,
+                        })
+            };
+            let index = match self.rows {
+                // a fixed count of rows is filled evenly, each patch going to the shortest row,
+                // which runs past the atlas edge once every row is full
+                Some(FixedRows::Count(_)) => rows
+                    .iter()
+                    .enumerate()
+                    .min_by(|(_, a), (_, b)| a.next_x.total_cmp(&b.next_x))
+                    .map_or(0, |(index, _)| index),
+                _ => match rows.iter().position(fits) {
+                    Some(index) => index,
+                    None => {
+                        rows.push(Row {
+                            height: match self.rows {
+                                Some(FixedRows::Height(height)) => height,
+                                _ => 0.,
+                            },
+                            next_x: padding,
+                            members: Vec::new(),
+                        });
+                        rows.len() - 1
+                    }
+                },
+            };
+
+            let row = &mut rows[index];
+            lefts.push(row.next_x);
+            row.height = row.height.max(patch.height());
+            row.next_x += patch.width() + padding;
+            row.members.push(i);
+        }
+
+        let mut result = patches.clone();
+        let mut top = padding;
+        for row in &rows {
+            for &i in &row.members {
+                result[i] = patches[i].with_left_and_top(lefts[i], top);
+            }
+            top += row.height + padding;
+        }
+        result
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Rect {
+    x: f32,
+    y: f32,
+    w: f32,
+    h: f32,
+}
+
+impl Rect {
+    fn new(x: f32, y: f32, w: f32, h: f32) -> Rect {
+        Rect { x, y, w, h }
+    }
+}
+
+enum GuillotineNode {
+    Empty(Rect),
+    Filled,
+    Split {
+        right: Box<GuillotineNode>,
+        below: Box<GuillotineNode>,
+    },
+}
+
+impl GuillotineNode {
+    fn empty_rects(&self) -> Vec<Rect> {
+        match self {
+            GuillotineNode::Empty(rect) => vec![*rect],
+            GuillotineNode::Filled => Vec::new(),
+            GuillotineNode::Split { right, below } => {
+                let mut rects = right.empty_rects();
+                rects.extend(below.empty_rects());
+                rects
+            }
+        }
+    }
+
+    // returns the top-left of the space claimed for a `width` x `height` patch, if any
+    fn insert(&mut self, width: f32, height: f32) -> Option<Vec2> {
+        match self {
+            GuillotineNode::Filled => None,
+            GuillotineNode::Split { right, below } => right
+                .insert(width, height)
+                .or_else(|| below.insert(width, height)),
+            GuillotineNode::Empty(rect) => {
+                if width > rect.w || height > rect.h {
+                    return None;
+                }
+
+                let position = Vec2::new(rect.x, rect.y);
+                *self = if width == rect.w && height == rect.h {
+                    GuillotineNode::Filled
+                } else {
+                    GuillotineNode::Split {
+                        right: Box::new(GuillotineNode::Empty(Rect::new(
+                            rect.x + width,
+                            rect.y,
+                            rect.w - width,
+                            height,
+                        ))),
+                        below: Box::new(GuillotineNode::Empty(Rect::new(
+                            rect.x,
+                            rect.y + height,
+                            rect.w,
+                            rect.h - height,
+                        ))),
+                    }
+                };
+                Some(position)
+            }
+        }
+    }
+}
+
+pub struct GuillotineStage;
+
+impl PackStage for GuillotineStage {
+    fn name(&self) -> &'static str {
+        "Guillotine"
+    }
+
+    fn apply(&self, patches: Vec<Patch>, config: PackingConfig) -> Vec<Patch> {
+        Self::pack(patches, config).0
+    }
+
+    fn free_rects(&self, patches: Vec<Patch>, config: PackingConfig) -> Vec<Patch> {
+        Self::pack(patches, config).1
+    }
+}
+
+impl GuillotineStage {
+    // returns the packed patches and the free space left over once they're placed
+    fn pack(patches: Vec<Patch>, config: PackingConfig) -> (Vec<Patch>, Vec<Patch>) {
+        let padding = config.padding;
+        let mut sorted_by_area = patches;
+        sorted_by_area.sort_by(by_area_then_width);
+
+        // as with MaxRects, nodes are sized to include padding on the right and bottom of each
+        // patch, and the tree is allowed to grow past the atlas bottom rather than drop patches
+        let stacked_height: f32 = sorted_by_area.iter().map(|p| p.height() + padding).sum();
+        let mut root = GuillotineNode::Empty(Rect::new(
+            padding,
+            padding,
+            config.width - padding,
+            (config.height - padding).max(stacked_height),
+        ));
+
+        let mut result: Vec<Patch> = Vec::new();
+        for patch in sorted_by_area {
+            result.push(
+                match root.insert(patch.width() + padding, patch.height() + padding) {
+                    Some(position) => patch.with_left_and_top(position.x, position.y),
+                    None => patch,
+                },
+            );
+        }
+
+        (
+            result,
+            root.empty_rects()
+                .iter()
+                .map(|rect| MaxRectsStage::free_rect(rect.x, rect.y, rect.w, rect.h))
+                .collect(),
+        )
+    }
+}
+
+// a run of the skyline: everything above `y` between `x` and `x + width` is already claimed
+#[derive(Clone, Copy, Debug)]
+struct SkylineSegment {
+    x: f32,
+    y: f32,
+    width: f32,
+}
+
+pub struct SkylineStage;
+
+impl PackStage for SkylineStage {
+    fn name(&self) -> &'static str {
+        "Skyline"
+    }
+
+    fn apply(&self, patches: Vec<Patch>, config: PackingConfig) -> Vec<Patch> {
+        Self::pack(patches, config).0
+    }
+
+    fn free_rects(&self, patches: Vec<Patch>, config: PackingConfig) -> Vec<Patch> {
+        Self::pack(patches, config).1
+    }
+}
+
+impl SkylineStage {
+    // returns the packed patches and the free space left over once they're placed
+    fn pack(patches: Vec<Patch>, config: PackingConfig) -> (Vec<Patch>, Vec<Patch>) {
+        let padding = config.padding;
+
+        // as with MaxRects, placements reserve one padding on the right and bottom of each patch
+        let mut skyline = vec![SkylineSegment {
+            x: padding,
+            y: padding,
+            width: config.width - padding,
+        }];
+
+        let mut result = Vec::new();
+        for patch in &patches {
+            let width = patch.width() + padding;
+            let height = patch.height() + padding;
+
+            // pick the spot closest to the top of the atlas, then the one wasting the least area
+            // under the patch, then the leftmost, which falls out of scanning left to right
+            let mut best: Option<(usize, f32, f32)> = None;
+            for i in 0..skyline.len() {
+                if let Some((top, waste)) = Self::fit(&skyline, i, width, config.width) {
+                    let better = best.is_none_or(|(_, best_top, best_waste)| {
+                        top < best_top || (top == best_top && waste < best_waste)
+                    });
+                    if better {
+                        best = Some((i, top, waste));
+                    }
+                }
+            }
+
+            if let Some((i, top, _)) = best {
+                let left = skyline[i].x;
+                Self::add_segment(
+                    &mut skyline,
+                    SkylineSegment {
+                        x: left,
+                        y: top + height,
+                        width,
+                    },
+                );
+                result.push(patch.with_left_and_top(left, top));
+            } else {
+                // too wide for the atlas; leave it where the previous stage put it
+                result.push(*patch);
+            }
+        }
+
+        (
+            result,
+            skyline
+                .iter()
+                .map(|segment| {
+                    MaxRectsStage::free_rect(
+                        segment.x,
+                        segment.y,
+                        segment.width,
+                        (config.height - segment.y).max(0.),
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    // if a `width` wide patch fits starting at segment `index`, returns the top it would rest at
+    // and the area left empty beneath it
+    fn fit(
+        skyline: &[SkylineSegment],
+        index: usize,
+        width: f32,
+        atlas_width: f32,
+    ) -> Option<(f32, f32)> {
+        let left = skyline[index].x;
+        if left + width > atlas_width + 1e-3 {
+            return None;
+        }
+
+        let right = left + width;
+        let spanned = skyline[index..]
+            .iter()
+            .take_while(|segment| segment.x < right);
+        let top = spanned
+            .clone()
+            .map(|segment| segment.y)
+            .fold(f32::MIN, f32::max);
+        let waste = spanned
+            .map(|segment| {
+                let overlap = (segment.x + segment.width).min(right) - segment.x.max(left);
+                (top - segment.y) * overlap
+            })
+            .sum();
+        Some((top, waste))
+    }
+
+    // raises the skyline under `placed`, trimming the segments it covers and merging neighbors
+    // left at the same height
+    fn add_segment(skyline: &mut Vec<SkylineSegment>, placed: SkylineSegment) {
+        let right = placed.x + placed.width;
+        let mut updated: Vec<SkylineSegment> = Vec::with_capacity(skyline.len() + 2);
+        for segment in skyline.iter() {
+            let segment_right = segment.x + segment.width;
+            if segment_right <= placed.x || segment.x >= right {
+                updated.push(*segment);
+                continue;
+            }
+            if segment.x < placed.x {
+                updated.push(SkylineSegment {
+                    width: placed.x - segment.x,
+                    ..*segment
+                });
+            }
+            if segment_right > right {
+                updated.push(SkylineSegment {
+                    x: right,
+                    width: segment_right - right,
+                    ..*segment
+                });
+            }
+        }
+
+        let at = updated.partition_point(|segment| segment.x < placed.x);
+        updated.insert(at, placed);
+
+        skyline.clear();
+        for segment in updated {
+            match skyline.last_mut() {
+                Some(last) if last.y == segment.y => last.width += segment.width,
+                _ => skyline.push(segment),
+            }
+        }
+    }
+}
+
+// rounds every edge down to a whole pixel; rounding both edges of every patch the same way keeps
+// their order, so patches that didn't overlap before snapping can at most come to touch. Edges
+// a hair under a whole pixel are float noise and round up to it rather than down past it; as
+// that could carry an edge into a neighbor, any pair snapping makes collide is left as packed
+pub struct SnapStage;
+
+// how far from a whole pixel an edge may be and still count as on it
+const SNAP_EPSILON: f32 = 1e-3;
+
+impl SnapStage {
+    fn snapped(value: f32) -> f32 {
+        let nearest = value.round();
+        if (value - nearest).abs() < SNAP_EPSILON {
+            nearest
+        } else {
+            value.floor()
+        }
+    }
+
+    fn snap(patch: &Patch) -> Patch {
+        let left = Self::snapped(patch.left());
+        let top = Self::snapped(patch.top());
+        if patch.footprint() == patch.extent {
+            // both edges snap, so neighbors sharing an edge still share it; a whole pixel sprite
+            // keeps its size wherever it sits
+            let extent = Vec2::new(
+                Self::snapped(patch.right()) - left,
+                Self::snapped(patch.bottom()) - top,
+            );
+            Patch { extent, ..*patch }.with_left_and_top(left, top)
+        } else {
+            // a tilted patch's extent doesn't line up with its footprint, so only move it
+            patch.with_left_and_top(left, top)
+        }
+    }
+}
+
+impl PackStage for SnapStage {
+    fn name(&self) -> &'static str {
+        "Snapped"
+    }
+
+    fn apply(&self, patches: Vec<Patch>, config: PackingConfig) -> Vec<Patch> {
+        // overlaps the packer left are reported as they are, not blamed on snapping
+        let packed_overlaps: HashSet<(i32, i32)> =
+            find_overlaps_within(&patches, config.overlap_epsilon)
+                .into_iter()
+                .collect();
+        let mut snapped: Vec<Patch> = patches.iter().map(Self::snap).collect();
+        loop {
+            let unsnap: HashSet<i32> = find_overlaps_within(&snapped, config.overlap_epsilon)
+                .into_iter()
+                .filter(|pair| !packed_overlaps.contains(pair))
+                .flat_map(|(a, b)| vec![a, b])
+                .collect();
+            if unsnap.is_empty() {
+                return snapped;
+            }
+            // each pass puts back at least one patch, so this ends with the packed layout at worst
+            for (patch, packed) in snapped.iter_mut().zip(&patches) {
+                if unsnap.contains(&patch.id) {
+                    *patch = *packed;
+                }
+            }
+        }
+    }
+}
+
+// lays patches out as uniform `tile` sized cells, left to right and top to bottom in the order
+// they arrive, as many to a row as fit within the padding
+pub struct GridTileStage {
+    pub tile: Vec2,
+}
+
+impl PackStage for GridTileStage {
+    fn name(&self) -> &'static str {
+        "Gridded"
+    }
+
+    fn apply(&self, patches: Vec<Patch>, config: PackingConfig) -> Vec<Patch> {
+        // patches arrive extruded, so the tiles grow by the extrusion too
+        let cell = self.tile + Vec2::splat(2. * config.extrude);
+        let cols = Self::cols(cell, &config);
+        patches
+            .iter()
+            .enumerate()
+            .map(|(i, patch)| {
+                let (row, col) = (i / cols, i % cols);
+                Patch {
+                    extent: cell,
+                    rotation: patch.source_rotation,
+                    ..*patch
+                }
+                .with_left_and_top(
+                    config.padding + col as f32 * (cell.x + config.padding),
+                    config.padding + row as f32 * (cell.y + config.padding),
+                )
+            })
+            .collect()
+    }
+}
+
+impl GridTileStage {
+    // at least one, so tiles wider than the atlas still go somewhere
+    fn cols(cell: Vec2, config: &PackingConfig) -> usize {
+        (((config.width - config.padding) / (cell.x + config.padding)).floor() as usize).max(1)
+    }
+}
+
+// the (row, column) of the grid cell a patch was laid out in by uniform tiling, if it was
+pub fn tile_cell(patch: &Patch, config: &PackingConfig) -> Option<(i32, i32)> {
+    let cell = config.tile? + Vec2::splat(2. * config.extrude);
+    let origin = Vec2::new(patch.left(), patch.top()) - Vec2::splat(config.extrude + config.border);
+    let index = origin / (cell + Vec2::splat(config.padding));
+    Some((index.y.round() as i32, index.x.round() as i32))
+}
+
+// which parts of a sprite are opaque, downsampled to square cells of `cell_size` source pixels;
+// a cell is solid if any pixel in it is. Cells are in the patch's unrotated source pixels and
+// scale with its extent, so a mask still fits a patch which was scaled down
+#[cfg(feature = "alpha-mask")]
+#[derive(Clone, Debug)]
+pub struct OccupancyMask {
+    pub width: usize,
+    pub height: usize,
+    pub cell_size: usize,
+    pub solid: Vec<bool>,
+}
+
+#[cfg(feature = "alpha-mask")]
+impl OccupancyMask {
+    // `alpha` holds one byte per pixel, row by row
+    pub fn from_alpha(width: usize, height: usize, alpha: &[u8], cell_size: usize) -> Self {
+        let cell_size = cell_size.max(1);
+        let cols = width.div_ceil(cell_size);
+        let rows = height.div_ceil(cell_size);
+        let mut solid = vec![false; cols * rows];
+        for (i, _) in alpha.iter().enumerate().filter(|&(_, &a)| a != 0) {
+            let (x, y) = (i % width, i / width);
+            solid[(y / cell_size) * cols + x / cell_size] = true;
+        }
+        Self {
+            width,
+            height,
+            cell_size,
+            solid,
+        }
+    }
+
+    fn cols(&self) -> usize {
+        self.width.div_ceil(self.cell_size)
+    }
+
+    // the solid cells as (min, max) corners in atlas space, turned with the patch
+    fn solid_rects(&self, patch: &Patch) -> Vec<(Vec2, Vec2)> {
+        let extent = patch.unrotated_extent();
+        let scale = Vec2::new(extent.x / self.width as f32, extent.y / self.height as f32);
+        let cols = self.cols();
+        let (sin, cos) = patch.rotation.sin_cos();
+        let turn = |v: Vec2| Vec2::new(v.x * cos - v.y * sin, v.x * sin + v.y * cos);
+        self.solid
+            .iter()
+            .enumerate()
+            .filter(|&(_, &solid)| solid)
+            .map(|(i, _)| {
+                let (col, row) = (i % cols, i / cols);
+                let min = Vec2::new((col * self.cell_size) as f32, (row * self.cell_size) as f32);
+                let max = Vec2::new(
+                    (((col + 1) * self.cell_size).min(self.width)) as f32,
+                    (((row + 1) * self.cell_size).min(self.height)) as f32,
+                );
+                let a = patch.center + turn(min * scale - extent / 2.);
+                let b = patch.center + turn(max * scale - extent / 2.);
+                (a.min(b), a.max(b))
+            })
+            .collect()
+    }
+}
+
+// whether two patches' solid regions come within `gap` of each other; a patch without a mask is
+// solid across its whole footprint
+#[cfg(feature = "alpha-mask")]
+pub fn masks_intersect(
+    a: &Patch,
+    a_mask: Option<&OccupancyMask>,
+    b: &Patch,
+    b_mask: Option<&OccupancyMask>,
+    gap: f32,
+) -> bool {
+    let footprint = |p: &Patch| {
+        vec![(
+            Vec2::new(p.left(), p.top()),
+            Vec2::new(p.right(), p.bottom()),
+        )]
+    };
+    let a_rects = a_mask.map_or_else(|| footprint(a), |mask| mask.solid_rects(a));
+    let b_rects = b_mask.map_or_else(|| footprint(b), |mask| mask.solid_rects(b));
+    a_rects.iter().any(|(a_min, a_max)| {
+        b_rects.iter().any(|(b_min, b_max)| {
+            a_min.x < b_max.x + gap
+                && a_max.x + gap > b_min.x
+                && a_min.y < b_max.y + gap
+                && a_max.y + gap > b_min.y
+        })
+    })
+}
+
+// places each patch at the topmost, then leftmost, spot where its solid cells stay `padding`
+// clear of every patch placed so far, so sprites nest into each other's transparent corners.
+// candidate spots are a grid as fine as the smallest mask cell, and like MaxRects it grows past
+// the atlas bottom rather than drop a patch
+#[cfg(feature = "alpha-mask")]
+pub struct MaskPackStage {
+    pub masks: HashMap<i32, OccupancyMask>,
+}
+
+#[cfg(feature = "alpha-mask")]
+impl PackStage for MaskPackStage {
+    fn name(&self) -> &'static str {
+        "Mask Packed"
+    }
+
+    fn apply(&self, patches: Vec<Patch>, config: PackingConfig) -> Vec<Patch> {
+        let padding = config.padding;
+        let step = patches
+            .iter()
+            .filter_map(|p| {
+                let mask = self.masks.get(&p.id)?;
+                let extent = p.unrotated_extent();
+                Some(
+                    (mask.cell_size as f32 * extent.x / mask.width as f32)
+                        .min(mask.cell_size as f32 * extent.y / mask.height as f32),
+                )
+            })
+            .fold(8f32, f32::min)
+            .max(1.);
+
+        let mut placed: Vec<Patch> = Vec::new();
+        for patch in &patches {
+            let mask = self.masks.get(&patch.id);
+            let fits = |candidate: &Patch| {
+                placed.iter().all(|other| {
+                    let near = candidate.left() < other.right() + padding
+                        && candidate.right() + padding > other.left()
+                        && candidate.top() < other.bottom() + padding
+                        && candidate.bottom() + padding > other.top();
+                    !near
+                        || !masks_intersect(
+                            candidate,
+                            mask,
+                            other,
+                            self.masks.get(&other.id),
+                            padding,
+                        )
+                })
+            };
+
+            let max_x = (config.width - padding - patch.width()).max(padding);
+            let mut y = padding;
+            let spot = loop {
+                let mut x = padding;
+                let found = loop {
+                    let candidate = patch.with_left_and_top(x, y);
+                    if fits(&candidate) {
+                        break Some(candidate);
+                    }
+                    if x >= max_x {
+                        break None;
+                    }
+                    x = (x + step).min(max_x);
+                };
+                if let Some(spot) = found {
+                    break spot;
+                }
+                y += step;
+            };
+            placed.push(spot);
+        }
+        placed
+    }
+}
+
+#[cfg(feature = "alpha-mask")]
+impl Pipeline {
+    // sorts by area, then packs by mask, for patches loaded with alpha masks
+    pub fn for_masks(masks: HashMap<i32, OccupancyMask>) -> Pipeline {
+        Pipeline {
+            stages: vec![
+                Box::new(SortStage {
+                    order: SortOrder::AreaDesc,
+                }),
+                Box::new(MaskPackStage { masks }),
+                Box::new(SnapStage),
+            ],
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////////
+
+// pairs of ids of patches overlapping by more than `DEFAULT_OVERLAP_EPSILON`
+pub fn find_overlaps(patches: &[Patch]) -> Vec<(i32, i32)> {
+    find_overlaps_within(patches, DEFAULT_OVERLAP_EPSILON)
+}
+
+pub fn find_overlaps_within(patches: &[Patch], epsilon: f32) -> Vec<(i32, i32)> {
+    let mut overlaps = Vec::new();
+    for (i, a) in patches.iter().enumerate() {
+        for b in &patches[i + 1..] {
+            if a.overlaps_by(b, epsilon) {
+                overlaps.push((a.id, b.id));
+            }
+        }
+    }
+    overlaps
+}
+
+// the min and max corners of the smallest rect holding every patch, which is the size a packed
+// atlas could be cropped to; zero-sized at the origin when there are no patches
+pub fn bounding_box(patches: &[Patch]) -> (Vec2, Vec2) {
+    if patches.is_empty() {
+        return (Vec2::ZERO, Vec2::ZERO);
+    }
+
+    let left = patches.iter().map(|p| p.left()).fold(f32::MAX, f32::min);
+    let top = patches.iter().map(|p| p.top()).fold(f32::MAX, f32::min);
+    let right = patches.iter().map(|p| p.right()).fold(f32::MIN, f32::max);
+    let bottom = patches.iter().map(|p| p.bottom()).fold(f32::MIN, f32::max);
+    (Vec2::new(left, top), Vec2::new(right, bottom))
+}
+
+// how far, as a fraction of the target, a layout's shape may stray from the aspect ratio it was
+// fit to and still count as fitting it
+pub const ASPECT_TOLERANCE: f32 = 0.1;
+
+// the config with an atlas width and height fitting `patches` to roughly `aspect`, width over
+// height. `pipeline` packs them at a range of widths around the one a perfectly filled atlas
+// would have; of the layouts within tolerance of the aspect the smallest is kept, and if none
+// are, the one closest to it. the height is however tall the chosen layout came out
+pub fn fit_to_aspect(
+    patches: &[Patch],
+    config: PackingConfig,
+    pipeline: &Pipeline,
+    aspect: f32,
+) -> PackingConfig {
+    const CANDIDATES: i32 = 48;
+    if patches.is_empty() || aspect.is_nan() || aspect <= 0. {
+        return config;
+    }
+
+    let margin = config.padding + 2. * config.extrude;
+    let area: f32 = patches
+        .iter()
+        .map(|p| (p.width() + margin) * (p.height() + margin))
+        .sum();
+    let (narrowest, unbounded) = sweep_limits(patches, &config);
+
+    let ideal = (area * aspect).sqrt();
+    let mut best: Option<(bool, f32, PackingConfig)> = None;
+    for i in 0..=CANDIDATES {
+        // from half to twice the ideal width, spaced evenly in scale
+        let width = (ideal * 0.5 * 4f32.powf(i as f32 / CANDIDATES as f32))
+            .round()
+            .max(narrowest);
+        let candidate = PackingConfig {
+            width,
+            height: unbounded,
+            ..config
+        };
+        let packed = pipeline.run(patches.to_vec(), candidate);
+        let height = bounding_box(&packed).1.y + config.extrude + config.border;
+        let miss = ((width / height) / aspect - 1.).abs();
+        let fits = miss <= ASPECT_TOLERANCE;
+        let score = if fits { width * height } else { miss };
+        let better = match best {
+            None => true,
+            Some((best_fits, best_score, _)) => {
+                (fits && !best_fits) || (fits == best_fits && score < best_score)
+            }
+        };
+        if better {
+            best = Some((
+                fits,
+                score,
+                PackingConfig {
+                    width,
+                    height,
+                    ..config
+                },
+            ));
+        }
+    }
+    best.map_or(config, |(_, _, fitted)| fitted)
+}
+
+// the narrowest atlas the widest patch fits across, and a height tall enough for any layout at
+// any width from there, so packers bounded by the atlas height never run out of room
+fn sweep_limits(patches: &[Patch], config: &PackingConfig) -> (f32, f32) {
+    let margin = config.padding + 2. * config.extrude;
+    let narrowest = patches
+        .iter()
+        .map(|p| p.width() + 2. * config.extrude)
+        .fold(0., f32::max)
+        + 2. * config.border;
+    let unbounded = patches
+        .iter()
+        .map(|p| p.width().max(p.height()) + margin)
+        .sum::<f32>()
+        + 2. * config.border;
+    (narrowest, unbounded)
+}
+
+// how many atlas widths `fit_to_area` packs at
+pub const AREA_SWEEP_STEPS: i32 = 64;
+
+// the config with the atlas width, and the height its layout packs to, which takes the least
+// area for `patches`. widths are swept in even steps from the widest patch to all patches side
+// by side, each packed with `pack`'s default pipeline since it runs many times over
+pub fn fit_to_area(patches: &[Patch], config: PackingConfig) -> PackingConfig {
+    if patches.is_empty() {
+        return config;
+    }
+
+    let (narrowest, unbounded) = sweep_limits(patches, &config);
+    let widest = patches
+        .iter()
+        .map(|p| p.width() + config.padding + 2. * config.extrude)
+        .sum::<f32>()
+        - config.padding
+        + 2. * config.border;
+    let mut best: Option<PackingConfig> = None;
+    for i in 0..=AREA_SWEEP_STEPS {
+        let width = (narrowest + (widest - narrowest) * i as f32 / AREA_SWEEP_STEPS as f32).ceil();
+        let candidate = PackingConfig {
+            width,
+            height: unbounded,
+            ..config
+        };
+        let packed = pack(candidate, patches.to_vec());
+        let height = (bounding_box(&packed).1.y + config.extrude + config.border).ceil();
+        if best.is_none_or(|best| width * height < best.width * best.height) {
+            best = Some(PackingConfig {
+                width,
+                height,
+                ..config
+            });
+        }
+    }
+    best.unwrap_or(config)
+}
+
+// the area the patches themselves cover, not counting padding or the gaps between them
+pub fn sprite_area(patches: &[Patch]) -> f32 {
+    patches.iter().map(|p| p.width() * p.height()).sum()
+}
+
+pub fn fill_ratio(patches: &[Patch]) -> f32 {
+    if patches.is_empty() {
+        return 0.;
+    }
+
+    let used_area = sprite_area(patches);
+    let (min, max) = bounding_box(patches);
+    let bounding_area = (max.x - min.x) * (max.y - min.y);
+
+    if bounding_area > 0. {
+        used_area / bounding_area
+    } else {
+        0.
+    }
+}
+
+// the smallest power-of-two atlas which holds every patch
+pub fn snap_pot(patches: &[Patch]) -> (u32, u32) {
+    let right = patches.iter().map(|p| p.right()).fold(0., f32::max);
+    let bottom = patches.iter().map(|p| p.bottom()).fold(0., f32::max);
+    (
+        (right.ceil() as u32).next_power_of_two(),
+        (bottom.ceil() as u32).next_power_of_two(),
+    )
+}
+
+pub fn terminal_overlaps(walk: &PipelineWalk) -> Option<Vec<(i32, i32)>> {
+    if walk.is_terminal() {
+        let extruded: Vec<Patch> = walk
+            .current()
+            .patches
+            .iter()
+            .map(|p| p.extruded(walk.config.extrude))
+            .collect();
+        Some(find_overlaps_within(&extruded, walk.config.overlap_epsilon))
+    } else {
+        None
+    }
+}
+
+// one atlas texture's worth of packed patches
+#[derive(Clone)]
+pub struct Page {
+    pub width: f32,
+    pub height: f32,
+    pub patches: Vec<Patch>,
+}
+
+impl Page {
+    pub fn contains(&self, patch: &Patch) -> bool {
+        patch.left() >= -1e-3
+            && patch.top() >= -1e-3
+            && patch.right() <= self.width + 1e-3
+            && patch.bottom() <= self.height + 1e-3
+    }
+}
+
+// the patch under `point`, preferring the last (drawn on top) where patches overlap
+pub fn patch_at(patches: &[Patch], point: Vec2) -> Option<&Patch> {
+    patches.iter().rev().find(|p| p.contains_point(point))
+}
+
+// a patch list as a table with right-aligned columns, for reading exact positions off a layout
+pub fn patch_table(patches: &[Patch]) -> String {
+    let mut rows = vec![[
+        "id".to_string(),
+        "left".to_string(),
+        "top".to_string(),
+        "width".to_string(),
+        "height".to_string(),
+        "rotation".to_string(),
+    ]];
+    for patch in patches {
+        rows.push([
+            patch.id.to_string(),
+            format!("{:.3}", patch.left()),
+            format!("{:.3}", patch.top()),
+            format!("{:.3}", patch.width()),
+            format!("{:.3}", patch.height()),
+            format!("{:.3}", patch.rotation.to_degrees()),
+        ]);
+    }
+
+    let mut widths = [0; 6];
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row.iter()) {
+            *width = (*width).max(cell.len());
+        }
+    }
+    let mut table = String::new();
+    for row in &rows {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(widths.iter())
+            .map(|(cell, &width)| format!("{:>width$}", cell, width = width))
+            .collect();
+        table.push_str(&cells.join("  "));
+        table.push('\n');
+    }
+    table
+}
+
+// the patches hanging off the bottom of an atlas, and how far past it the lowest one reaches
+pub struct Overflow {
+    pub amount: f32,
+    pub ids: Vec<i32>,
+}
+
+// None when every patch ends within `height`, allowing the same slack as `Page::contains`
+pub fn vertical_overflow(patches: &[Patch], height: f32) -> Option<Overflow> {
+    let ids: Vec<i32> = patches
+        .iter()
+        .filter(|p| p.bottom() > height + 1e-3)
+        .map(|p| p.id)
+        .collect();
+    if ids.is_empty() {
+        return None;
+    }
+    let bottom = patches.iter().map(|p| p.bottom()).fold(height, f32::max);
+    Some(Overflow {
+        amount: bottom - height,
+        ids,
+    })
+}
+
+// splits a packed layout into atlas-sized pages: whatever spills off a page is packed again by
+// `pipeline` onto the next one, until everything has a page or nothing more will fit
+pub fn paginate(patches: Vec<Patch>, config: PackingConfig, pipeline: &Pipeline) -> Vec<Page> {
+    let mut pages = Vec::new();
+    let mut packed = patches;
+    loop {
+        let mut page = Page {
+            width: config.width,
+            height: config.height,
+            patches: Vec::new(),
+        };
+        let (fits, overflow): (Vec<Patch>, Vec<Patch>) =
+            packed.into_iter().partition(|patch| page.contains(patch));
+        if fits.is_empty() {
+            // nothing more will fit, so leave the rest hanging off a final page
+            if !overflow.is_empty() {
+                log_warn!("{} patches fit on no page", overflow.len());
+            }
+            page.patches = overflow;
+            pages.push(page);
+            break;
+        }
+
+        page.patches = fits;
+        pages.push(page);
+        if overflow.is_empty() {
+            break;
+        }
+        log_trace!(
+            "page {} holds {} patches, {} left over",
+            pages.len(),
+            pages[pages.len() - 1].patches.len(),
+            overflow.len()
+        );
+        packed = pipeline.run(overflow, config);
+    }
+    pages
+}
+
+// each page cropped to its patches, moved so their extruded edges come no closer than `border`
+// to the page's top left, with `border` kept on the right and bottom too; the unused margin a
+// packer leaves isn't worth shipping in the texture
+pub fn trim_pages(pages: &[Page], config: &PackingConfig) -> Vec<Page> {
+    pages
+        .iter()
+        .map(|page| {
+            if page.patches.is_empty() {
+                return page.clone();
+            }
+            let extruded: Vec<Patch> = page
+                .patches
+                .iter()
+                .map(|p| p.extruded(config.extrude))
+                .collect();
+            let (min, max) = bounding_box(&extruded);
+            let margin = Vec2::splat(config.border);
+            let offset = margin - min;
+            let size = max - min + 2. * margin;
+            Page {
+                width: size.x,
+                height: size.y,
+                patches: page
+                    .patches
+                    .iter()
+                    .map(|p| Patch {
+                        center: p.center + offset,
+                        ..*p
+                    })
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+// the size reported for a whole atlas: the largest of its pages, which for untrimmed pages is
+// simply the configured size
+fn atlas_size(pages: &[Page], config: &PackingConfig) -> Vec2 {
+    if pages.is_empty() {
+        return Vec2::new(config.width, config.height);
+    }
+    pages
+        .iter()
+        .map(|page| Vec2::new(page.width, page.height))
+        .fold(Vec2::ZERO, Vec2::max)
+}
+
+// a finished layout is split into pages; anything earlier is shown whole on a single page
+pub fn walk_pages(walk: &PipelineWalk) -> Vec<Page> {
+    let patches = walk.current().patches.clone();
+    if walk.is_terminal() && !patches.is_empty() {
+        paginate(patches, walk.config, &walk.pipeline)
+    } else {
+        vec![Page {
+            width: walk.config.width,
+            height: walk.config.height,
+            patches,
+        }]
+    }
+}
+
+// how well a layout packed, for scripts gating a build on it. areas and the fill ratio are
+// summed over pages, each page measured against its own patches' bounds as the HUD does, so a
+// single page reports the HUD's numbers; `steps` gives each stage's timing, skipping the first
+// step since nothing ran to produce it
+pub fn export_stats(pages: &[Page], config: &PackingConfig, steps: &[Step]) -> String {
+    let patches: Vec<&Patch> = pages.iter().flat_map(|page| &page.patches).collect();
+    let used_area: f32 = pages.iter().map(|page| sprite_area(&page.patches)).sum();
+    let bounding_area: f32 = pages
+        .iter()
+        .filter(|page| !page.patches.is_empty())
+        .map(|page| {
+            let (min, max) = bounding_box(&page.patches);
+            (max.x - min.x) * (max.y - min.y)
+        })
+        .sum();
+    let fill = if bounding_area > 0. {
+        used_area / bounding_area
+    } else {
+        0.
+    };
+    let overflow = pages
+        .iter()
+        .filter_map(|page| vertical_overflow(&page.patches, page.height))
+        .map(|overflow| overflow.amount)
+        .fold(0., f32::max);
+    let stages: Vec<String> = steps
+        .iter()
+        .skip(1)
+        .map(|step| {
+            format!(
+                "    {{ \"name\": {}, \"ms\": {} }}",
+                json::escape(step.name),
+                step.elapsed.as_secs_f64() * 1000.
+            )
+        })
+        .collect();
+    let size = atlas_size(pages, config);
+    format!(
+        "{{\n  \"patch_count\": {},\n  \"page_count\": {},\n  \"atlas_width\": {},\n  \"atlas_height\": {},\n  \"used_area\": {},\n  \"bounding_area\": {},\n  \"fill_ratio\": {},\n  \"rotated\": {},\n  \"max_overflow\": {},\n  \"stages\": [\n{}\n  ]\n}}\n",
+        patches.len(),
+        pages.len(),
+        size.x,
+        size.y,
+        used_area,
+        bounding_area,
+        fill,
+        patches.iter().filter(|p| is_turned(p)).count(),
+        overflow,
+        stages.join(",\n")
+    )
+}
+
+// the layout of exported atlas json, bumped whenever a change would trip up existing parsers
+pub const ATLAS_JSON_VERSION: i64 = 1;
+
+pub fn export_atlas_json(
+    pages: &[Page],
+    config: &PackingConfig,
+    names: &HashMap<i32, String>,
+) -> String {
+    // grouped frames also report where they sit within their group's strip on the page
+    let mut group_origins: HashMap<(usize, u32), Vec2> = HashMap::new();
+    for (index, page) in pages.iter().enumerate() {
+        for patch in &page.patches {
+            if let Some(group) = patch.group {
+                let corner = Vec2::new(patch.left(), patch.top());
+                group_origins
+                    .entry((index, group))
+                    .and_modify(|origin| *origin = origin.min(corner))
+                    .or_insert(corner);
+            }
+        }
+    }
+
+    let frames: Vec<String> = pages
+        .iter()
+        .enumerate()
+        .flat_map(|(index, page)| page.patches.iter().map(move |patch| (index, patch)))
+        .map(|(page, patch)| {
+            let group = match patch.group {
+                Some(group) => {
+                    let origin = group_origins[&(page, group)];
+                    format!(
+                        ", \"group\": {}, \"group_x\": {}, \"group_y\": {}",
+                        group,
+                        (patch.left() - origin.x).round() as i32,
+                        (patch.top() - origin.y).round() as i32
+                    )
+                }
+                None => String::new(),
+            };
+            let cell = match tile_cell(patch, config) {
+                Some((row, col)) => format!(", \"row\": {}, \"col\": {}", row, col),
+                None => String::new(),
+            };
+            let name = match names.get(&patch.id) {
+                Some(name) => format!(", \"name\": {}", json::escape(name)),
+                None => String::new(),
+            };
+            format!(
+                "    {{ \"id\": {}, \"page\": {}, \"x\": {}, \"y\": {}, \"width\": {}, \"height\": {}, \"rotated\": {}, \"source_width\": {}, \"source_height\": {}, \"source_x\": {}, \"source_y\": {}{}{}{} }}",
+                patch.id,
+                page,
+                patch.left().round() as i32,
+                patch.top().round() as i32,
+                patch.width().round() as i32,
+                patch.height().round() as i32,
+                is_turned(patch),
+                patch.source_extent.x.round() as i32,
+                patch.source_extent.y.round() as i32,
+                patch.source_offset.x.round() as i32,
+                patch.source_offset.y.round() as i32,
+                group,
+                cell,
+                name
+            )
+        })
+        .collect();
+
+    // each page's own image and size, which differ from the atlas's once pages are trimmed
+    let page_blocks: Vec<String> = pages
+        .iter()
+        .enumerate()
+        .map(|(index, page)| {
+            format!(
+                "    {{ \"page\": {}, \"image\": {}, \"width\": {}, \"height\": {} }}",
+                index,
+                json::escape(&page_image_name(index, pages.len())),
+                page.width.round() as i32,
+                page.height.round() as i32
+            )
+        })
+        .collect();
+
+    let size = atlas_size(pages, config);
+    format!(
+        "{{\n  \"version\": {},\n  \"atlas\": {{ \"width\": {}, \"height\": {}, \"pages\": {} }},\n  \"pages\": [\n{}\n  ],\n  \"frames\": [\n{}\n  ]\n}}\n",
+        ATLAS_JSON_VERSION,
+        size.x.round() as i32,
+        size.y.round() as i32,
+        pages.len(),
+        page_blocks.join(",\n"),
+        frames.join(",\n")
+    )
+}
+
+// frames are named after their source image when there is one
+fn frame_name(names: &HashMap<i32, String>, id: i32) -> String {
+    names
+        .get(&id)
+        .cloned()
+        .unwrap_or_else(|| format!("sprite_{}.png", id))
+}
+
+// libgdx regions are named after their source image without its extension
+fn region_name(names: &HashMap<i32, String>, id: i32) -> String {
+    match names.get(&id) {
+        Some(name) => name
+            .rsplit_once('.')
+            .map_or(name.as_str(), |(stem, _)| stem)
+            .to_string(),
+        None => format!("sprite_{}", id),
+    }
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// the plist format read by cocos2d and SpriteKit; rotated frames are stored turned 90 degrees
+// on the sheet, but their frame size is reported in the sprite's own, unrotated orientation
+pub fn export_atlas_plist(
+    pages: &[Page],
+    config: &PackingConfig,
+    names: &HashMap<i32, String>,
+) -> String {
+    let frames: Vec<String> = pages
+        .iter()
+        .enumerate()
+        .flat_map(|(index, page)| page.patches.iter().map(move |patch| (index, patch)))
+        .map(|(page, patch)| {
+            let size = patch.unrotated_extent();
+            let (width, height) = (size.x.round() as i32, size.y.round() as i32);
+            // cocos2d measures the offset from the source's center to the trimmed frame's
+            // center, with y pointing up
+            let source = patch.source_extent;
+            let offset = Vec2::new(
+                patch.source_offset.x + size.x / 2. - source.x / 2.,
+                source.y / 2. - patch.source_offset.y - size.y / 2.,
+            );
+            format!(
+                "            <key>{}</key>\n            <dict>\n                <key>page</key>\n                <integer>{}</integer>\n                <key>frame</key>\n                <string>{{{{{},{}}},{{{},{}}}}}</string>\n                <key>offset</key>\n                <string>{{{},{}}}</string>\n                <key>rotated</key>\n                <{}/>\n                <key>sourceSize</key>\n                <string>{{{},{}}}</string>\n                <key>spriteSourceSize</key>\n                <string>{{{{{},{}}},{{{},{}}}}}</string>\n            </dict>",
+                xml_escape(&frame_name(names, patch.id)),
+                page,
+                patch.left().round() as i32,
+                patch.top().round() as i32,
+                width,
+                height,
+                offset.x.round() as i32,
+                offset.y.round() as i32,
+                is_turned(patch),
+                source.x.round() as i32,
+                source.y.round() as i32,
+                patch.source_offset.x.round() as i32,
+                patch.source_offset.y.round() as i32,
+                width,
+                height
+            )
+        })
+        .collect();
+
+    // the metadata names the first page's texture, as single page readers expect, and lists
+    // every page's texture and size for frames' "page" to refer to
+    let page_blocks: Vec<String> = pages
+        .iter()
+        .enumerate()
+        .map(|(index, page)| {
+            format!(
+                "                <dict>\n                    <key>textureFileName</key>\n                    <string>{}</string>\n                    <key>size</key>\n                    <string>{{{},{}}}</string>\n                </dict>",
+                page_image_name(index, pages.len()),
+                page.width.round() as i32,
+                page.height.round() as i32
+            )
+        })
+        .collect();
+
+    let size = atlas_size(pages, config);
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n<plist version=\"1.0\">\n    <dict>\n        <key>frames</key>\n        <dict>\n{}\n        </dict>\n        <key>metadata</key>\n        <dict>\n            <key>format</key>\n            <integer>2</integer>\n            <key>size</key>\n            <string>{{{},{}}}</string>\n            <key>textureFileName</key>\n            <string>{}</string>\n            <key>pages</key>\n            <array>\n{}\n            </array>\n        </dict>\n    </dict>\n</plist>\n",
+        frames.join("\n"),
+        size.x.round() as i32,
+        size.y.round() as i32,
+        page_image_name(0, pages.len()),
+        page_blocks.join("\n")
+    )
+}
+
+// one row per patch, rounded like the JSON export, for spreadsheets and scripts; patches
+// without a name leave it empty
+pub fn export_atlas_csv(pages: &[Page], names: &HashMap<i32, String>) -> String {
+    let mut csv = "id,name,x,y,width,height,rotated,page\n".to_string();
+    for (index, page) in pages.iter().enumerate() {
+        for patch in &page.patches {
+            csv.push_str(&format!(
+                "{},{},{},{},{},{},{},{}\n",
+                patch.id,
+                names
+                    .get(&patch.id)
+                    .map_or_else(String::new, |name| csv_field(name)),
+                patch.left().round() as i32,
+                patch.top().round() as i32,
+                patch.width().round() as i32,
+                patch.height().round() as i32,
+                is_turned(patch),
+                index
+            ));
+        }
+    }
+    csv
+}
+
+// quotes a field holding a comma, quote or line break, doubling any quotes
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+// libgdx's TextureAtlas text format; like the plist, a rotated region (turned 90 degrees
+// clockwise on the page) reports its unrotated size and occupies the swapped size on the page
+pub fn export_libgdx_atlas(
+    patches: &[Patch],
+    config: &PackingConfig,
+    names: &HashMap<i32, String>,
+    page_name: &str,
+) -> String {
+    let mut atlas = format!(
+        "\n{}\nsize: {},{}\nformat: RGBA8888\nfilter: Linear,Linear\nrepeat: none\n",
+        page_name,
+        config.width.round() as i32,
+        config.height.round() as i32
+    );
+    for patch in patches {
+        let size = patch.unrotated_extent();
+        let (width, height) = (size.x.round() as i32, size.y.round() as i32);
+        atlas.push_str(&format!(
+            "{}\n  rotate: {}\n  xy: {}, {}\n  size: {}, {}\n  orig: {}, {}\n  offset: {}, {}\n  index: -1\n",
+            region_name(names, patch.id),
+            is_turned(patch),
+            patch.left().round() as i32,
+            patch.top().round() as i32,
+            width,
+            height,
+            patch.source_extent.x.round() as i32,
+            patch.source_extent.y.round() as i32,
+            patch.source_offset.x.round() as i32,
+            // libgdx measures the offset up from the bottom of the source image
+            (patch.source_extent.y - patch.source_offset.y - size.y).round() as i32
+        ));
+    }
+    atlas
+}
+
+// one page as a sheet for Unity's Sprite Editor to slice as multiple sprites. Unity measures
+// rects up from the texture's bottom left, so y is flipped against the page height, and a
+// rotated sprite's rect is the space it takes on the page, its size turned on its side. the
+// pivot puts the source image's center where it was before trimming, as a fraction of the rect
+pub fn export_unity(
+    patches: &[Patch],
+    config: &PackingConfig,
+    names: &HashMap<i32, String>,
+    texture: &str,
+) -> String {
+    let sprites: Vec<String> = patches
+        .iter()
+        .map(|patch| {
+            let size = patch.unrotated_extent();
+            let source = patch.source_extent;
+            let pivot = Vec2::new(
+                (source.x / 2. - patch.source_offset.x) / size.x,
+                (patch.source_offset.y + size.y - source.y / 2.) / size.y,
+            );
+            format!(
+                "      {{ \"name\": {}, \"rect\": {{ \"x\": {}, \"y\": {}, \"width\": {}, \"height\": {} }}, \"pivot\": {{ \"x\": {}, \"y\": {} }}, \"border\": {{ \"x\": 0, \"y\": 0, \"z\": 0, \"w\": 0 }}, \"rotated\": {} }}",
+                json::escape(&region_name(names, patch.id)),
+                patch.left().round() as i32,
+                (config.height - patch.bottom()).round() as i32,
+                patch.width().round() as i32,
+                patch.height().round() as i32,
+                pivot.x,
+                pivot.y,
+                is_turned(patch)
+            )
+        })
+        .collect();
+    format!(
+        "  {{\n    \"texture\": {},\n    \"width\": {},\n    \"height\": {},\n    \"sprites\": [\n{}\n    ]\n  }}",
+        json::escape(texture),
+        config.width.round() as i32,
+        config.height.round() as i32,
+        sprites.join(",\n")
+    )
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    Json,
+    Plist,
+    Libgdx,
+    Csv,
+    Unity,
+}
+
+impl ExportFormat {
+    pub fn from_name(name: &str) -> Option<ExportFormat> {
+        match name {
+            "json" => Some(ExportFormat::Json),
+            "plist" => Some(ExportFormat::Plist),
+            "libgdx" => Some(ExportFormat::Libgdx),
+            "csv" => Some(ExportFormat::Csv),
+            "unity" => Some(ExportFormat::Unity),
+            _ => None,
+        }
+    }
+
+    pub fn file_name(self) -> &'static str {
+        match self {
+            ExportFormat::Json => "atlas.json",
+            ExportFormat::Plist => "atlas.plist",
+            ExportFormat::Libgdx => "atlas.atlas",
+            ExportFormat::Csv => "atlas.csv",
+            ExportFormat::Unity => "atlas.unity.json",
+        }
+    }
+}
+
+// the image file name for each page, numbered only when there's more than one
+pub fn page_image_name(index: usize, page_count: usize) -> String {
+    if page_count > 1 {
+        format!("page_{}.png", index)
+    } else {
+        "atlas.png".to_string()
+    }
+}
+
+pub fn write_atlas(
+    path: &Path,
+    pages: &[Page],
+    config: &PackingConfig,
+    names: &HashMap<i32, String>,
+    format: ExportFormat,
+) -> std::io::Result<()> {
+    let contents = match format {
+        ExportFormat::Json => export_atlas_json(pages, config, names),
+        ExportFormat::Csv => export_atlas_csv(pages, names),
+        ExportFormat::Plist => export_atlas_plist(pages, config, names),
+        // a libgdx atlas simply lists one page after another
+        ExportFormat::Libgdx => pages
+            .iter()
+            .enumerate()
+            .map(|(index, page)| {
+                export_libgdx_atlas(
+                    &page.patches,
+                    &PackingConfig {
+                        width: page.width,
+                        height: page.height,
+                        ..*config
+                    },
+                    names,
+                    &page_image_name(index, pages.len()),
+                )
+            })
+            .collect(),
+        // and a Unity export is an array of sheets, one per page
+        ExportFormat::Unity => {
+            let sheets: Vec<String> = pages
+                .iter()
+                .enumerate()
+                .map(|(index, page)| {
+                    export_unity(
+                        &page.patches,
+                        &PackingConfig {
+                            width: page.width,
+                            height: page.height,
+                            ..*config
+                        },
+                        names,
+                        &page_image_name(index, pages.len()),
+                    )
+                })
+                .collect();
+            format!("[\n{}\n]\n", sheets.join(",\n"))
+        }
+    };
+    log_info!(
+        "exporting {} patches on {} pages to {}",
+        pages.iter().map(|page| page.patches.len()).sum::<usize>(),
+        pages.len(),
+        path.display()
+    );
+    std::fs::write(path, contents)
+}
+
+pub fn save_session(path: &Path, patches: &[Patch], config: &PackingConfig) -> std::io::Result<()> {
+    let patches: Vec<String> = patches
+        .iter()
+        .map(|patch| {
+            format!(
+                "    {{ \"id\": {}, \"center\": [{}, {}], \"extent\": [{}, {}], \"rotation\": {}, \"source_extent\": [{}, {}], \"source_rotation\": {}, \"source_offset\": [{}, {}], \"group\": {} }}",
+                patch.id,
+                patch.center.x,
+                patch.center.y,
+                patch.extent.x,
+                patch.extent.y,
+                patch.rotation,
+                patch.source_extent.x,
+                patch.source_extent.y,
+                patch.source_rotation,
+                patch.source_offset.x,
+                patch.source_offset.y,
+                patch
+                    .group
+                    .map_or_else(|| "null".to_string(), |group| group.to_string())
+            )
+        })
+        .collect();
+
+    let json = format!(
+        "{{\n  \"config\": {{ \"width\": {}, \"height\": {}, \"padding\": {}, \"border\": {}, \"packer\": {}, \"sort\": {}, \"orientation\": {}, \"extrude\": {}, \"probe_columns\": {}, \"try_turns\": {}, \"overlap_epsilon\": {}, \"tile\": {}, \"fixed_rows\": {} }},\n  \"patches\": [\n{}\n  ]\n}}\n",
+        config.width,
+        config.height,
+        config.padding,
+        config.border,
+        json::escape(config.packer.name()),
+        json::escape(config.sort.name()),
+        json::escape(config.orientation.name()),
+        config.extrude,
+        config.probe_columns,
+        config.try_turns,
+        config.overlap_epsilon,
+        config
+            .tile
+            .map_or_else(|| "null".to_string(), |tile| format!("[{}, {}]", tile.x, tile.y)),
+        match config.fixed_rows {
+            Some(FixedRows::Count(count)) => format!("{{ \"count\": {} }}", count),
+            Some(FixedRows::Height(height)) => format!("{{ \"height\": {} }}", height),
+            None => "null".to_string(),
+        },
+        patches.join(",\n")
+    );
+    std::fs::write(path, json)
+}
+
+pub fn load_session(path: &Path) -> std::io::Result<(Vec<Patch>, PackingConfig)> {
+    let invalid = |message: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, message);
+    let document = json::parse(&std::fs::read_to_string(path)?).map_err(|e| invalid(&e))?;
+
+    let field = |value: &json::Value, key: &str| {
+        value
+            .get(key)
+            .and_then(json::Value::as_f32)
+            .ok_or_else(|| invalid(&format!("missing or invalid \"{}\"", key)))
+    };
+    let pair = |value: &json::Value, key: &str| {
+        match value.get(key).and_then(json::Value::as_array) {
+            Some([x, y]) => x.as_f32().zip(y.as_f32()).map(|(x, y)| Vec2::new(x, y)),
+            _ => None,
+        }
+        .ok_or_else(|| invalid(&format!("missing or invalid \"{}\"", key)))
+    };
+
+    let config_value = document
+        .get("config")
+        .ok_or_else(|| invalid("missing \"config\""))?;
+    let config = PackingConfig {
+        width: field(config_value, "width")?,
+        height: field(config_value, "height")?,
+        padding: field(config_value, "padding")?.max(0.),
+        // sessions saved before the border existed kept padding from the edges
+        border: match config_value.get("border") {
+            Some(_) => field(config_value, "border")?.max(0.),
+            None => field(config_value, "padding")?.max(0.),
+        },
+        packer: config_value
+            .get("packer")
+            .and_then(json::Value::as_str)
+            .and_then(Packer::from_name)
+            .ok_or_else(|| invalid("missing or invalid \"packer\""))?,
+        // sessions saved before sorting was configurable always sorted by height
+        sort: match config_value.get("sort") {
+            Some(sort) => sort
+                .as_str()
+                .and_then(SortOrder::from_name)
+                .ok_or_else(|| invalid("invalid \"sort\""))?,
+            None => SortOrder::HeightDesc,
+        },
+        // as were sessions saved before the orientation was configurable
+        orientation: match config_value.get("orientation") {
+            Some(orientation) => orientation
+                .as_str()
+                .and_then(Orientation::from_name)
+                .ok_or_else(|| invalid("invalid \"orientation\""))?,
+            None => Orientation::AlwaysPortrait,
+        },
+        extrude: match config_value.get("extrude") {
+            Some(_) => field(config_value, "extrude")?.max(0.),
+            None => 0.,
+        },
+        probe_columns: match config_value.get("probe_columns") {
+            Some(probe) => probe
+                .as_bool()
+                .ok_or_else(|| invalid("invalid \"probe_columns\""))?,
+            None => false,
+        },
+        try_turns: match config_value.get("try_turns") {
+            Some(turns) => turns
+                .as_bool()
+                .ok_or_else(|| invalid("invalid \"try_turns\""))?,
+            None => false,
+        },
+        overlap_epsilon: match config_value.get("overlap_epsilon") {
+            Some(_) => field(config_value, "overlap_epsilon")?.max(0.),
+            None => DEFAULT_OVERLAP_EPSILON,
+        },
+        tile: match config_value.get("tile") {
+            None | Some(json::Value::Null) => None,
+            Some(_) => Some(pair(config_value, "tile")?),
+        },
+        fixed_rows: match config_value.get("fixed_rows") {
+            None | Some(json::Value::Null) => None,
+            Some(rows) => match (rows.get("count"), rows.get("height")) {
+                (Some(count), None) => Some(FixedRows::Count(
+                    count
+                        .as_i64()
+                        .filter(|&count| count > 0)
+                        .ok_or_else(|| invalid("invalid \"fixed_rows\" count"))?
+                        as usize,
+                )),
+                (None, Some(_)) => Some(FixedRows::Height(field(rows, "height")?)),
+                _ => return Err(invalid("invalid \"fixed_rows\"")),
+            },
+        },
+    };
+
+    let mut patches = Vec::new();
+    for value in document
+        .get("patches")
+        .and_then(json::Value::as_array)
+        .ok_or_else(|| invalid("missing \"patches\""))?
+    {
+        let mut patch = Patch {
+            id: value
+                .get("id")
+                .and_then(json::Value::as_i64)
+                .ok_or_else(|| invalid("missing or invalid \"id\""))? as i32,
+            center: pair(value, "center")?,
+            extent: pair(value, "extent")?,
+            rotation: field(value, "rotation")?,
+            source_extent: Vec2::ZERO,
+            source_rotation: 0.,
+            source_offset: Vec2::ZERO,
+            group: None,
+        };
+        // sessions saved before source dimensions were tracked fall back to the unrotated size
+        patch.source_extent = match value.get("source_extent") {
+            Some(_) => pair(value, "source_extent")?,
+            None => patch.unrotated_extent(),
+        };
+        if value.get("source_rotation").is_some() {
+            patch.source_rotation = field(value, "source_rotation")?;
+        }
+        if value.get("source_offset").is_some() {
+            patch.source_offset = pair(value, "source_offset")?;
+        }
+        patch.group = match value.get("group") {
+            Some(json::Value::Null) | None => None,
+            Some(group) => Some(group.as_i64().ok_or_else(|| invalid("invalid \"group\""))? as u32),
+        };
+        patches.push(patch);
+    }
+
+    Ok((patches, config))
+}
+
+// a layout packed elsewhere, read back from the json exporter's schema; frames which can't be
+// read are left out and described in `problems` so the rest can still be inspected
+pub struct ImportedLayout {
+    pub patches: Vec<Patch>,
+    // the frames' names keyed by id, for those which have one
+    pub names: HashMap<i32, String>,
+    pub width: f32,
+    pub height: f32,
+    pub problems: Vec<String>,
+}
+
+// only fails when `text` isn't an exported atlas of a version this understands; anything else
+// wrong is a problem
+pub fn import_atlas_json(text: &str) -> Result<ImportedLayout, String> {
+    let document = json::parse(text)?;
+    if document.get("frames").is_none() && document.get("atlas").is_none() {
+        return Err("expected an exported atlas with \"atlas\" and \"frames\"".to_string());
+    }
+    // atlases exported before versioning match version 1
+    match document.get("version") {
+        None => {}
+        Some(version) if version.as_i64() == Some(ATLAS_JSON_VERSION) => {}
+        Some(version) => {
+            return Err(format!(
+                "unsupported atlas version {}, expected {}",
+                version
+                    .as_i64()
+                    .map_or_else(|| "(not a number)".to_string(), |v| v.to_string()),
+                ATLAS_JSON_VERSION
+            ))
+        }
+    }
+
+    let mut problems = Vec::new();
+    let mut names = HashMap::new();
+    let mut patches: Vec<Patch> = Vec::new();
+    let mut other_pages = 0;
+    let frames = match document.get("frames").and_then(json::Value::as_array) {
+        Some(frames) => frames,
+        None => {
+            problems.push("missing \"frames\"".to_string());
+            &[]
+        }
+    };
+    for (index, frame) in frames.iter().enumerate() {
+        let number = |key: &str| frame.get(key).and_then(json::Value::as_f32);
+        let (id, x, y, width, height) = match (
+            frame.get("id").and_then(json::Value::as_i64),
+            number("x"),
+            number("y"),
+            number("width"),
+            number("height"),
+        ) {
+            (Some(id), Some(x), Some(y), Some(width), Some(height)) => {
+                (id as i32, x, y, width, height)
+            }
+            _ => {
+                problems.push(format!(
+                    "frame {} is missing its id, position or size",
+                    index
+                ));
+                continue;
+            }
+        };
+        // pages would all be drawn over each other, so only the first is kept
+        if frame.get("page").and_then(json::Value::as_i64).unwrap_or(0) != 0 {
+            other_pages += 1;
+            continue;
+        }
+        if patches.iter().any(|p| p.id == id) {
+            problems.push(format!("frame {} repeats id {}", index, id));
+            continue;
+        }
+
+        let rotated = match frame.get("rotated") {
+            Some(rotated) => rotated.as_bool().unwrap_or_else(|| {
+                problems.push(format!("frame {} has an invalid \"rotated\"", index));
+                false
+            }),
+            None => false,
+        };
+        let mut patch = Patch {
+            id,
+            center: Vec2::ZERO,
+            extent: Vec2::new(width, height),
+            rotation: if rotated {
+                std::f32::consts::FRAC_PI_2
+            } else {
+                0.
+            },
+            source_extent: Vec2::ZERO,
+            source_rotation: 0.,
+            source_offset: Vec2::ZERO,
+            group: None,
+        }
+        .with_left_and_top(x, y);
+        // exporters which don't trim leave the source dimensions out
+        patch.source_extent = match number("source_width").zip(number("source_height")) {
+            Some((w, h)) => Vec2::new(w, h),
+            None => patch.unrotated_extent(),
+        };
+        if let Some((x, y)) = number("source_x").zip(number("source_y")) {
+            patch.source_offset = Vec2::new(x, y);
+        }
+        patch.group = frame
+            .get("group")
+            .and_then(json::Value::as_i64)
+            .map(|group| group as u32);
+        if let Some(name) = frame.get("name").and_then(json::Value::as_str) {
+            names.insert(patch.id, name.to_string());
+        }
+        patches.push(patch);
+    }
+    if other_pages > 0 {
+        problems.push(format!("{} frames on later pages left out", other_pages));
+    }
+
+    let atlas = document.get("atlas");
+    let size = |key: &str| atlas.and_then(|a| a.get(key)).and_then(json::Value::as_f32);
+    let (width, height) = match size("width").zip(size("height")) {
+        Some(size) => size,
+        None => {
+            problems.push("missing atlas size, using the frames' bounds".to_string());
+            let (_, max) = bounding_box(&patches);
+            (max.x, max.y)
+        }
+    };
+
+    Ok(ImportedLayout {
+        patches,
+        names,
+        width,
+        height,
+        problems,
+    })
+}
+
+// top left, top right, bottom right and bottom left corners of a rect rotated about its center
+pub fn rotated_corners(center: Vec2, size: Vec2, rotation: f32) -> [Vec2; 4] {
+    let (sin, cos) = rotation.sin_cos();
+    let half_x = Vec2::new(cos, sin) * (size.x / 2.);
+    let half_y = Vec2::new(-sin, cos) * (size.y / 2.);
+    [
+        center - half_x - half_y,
+        center + half_x - half_y,
+        center + half_x + half_y,
+        center - half_x + half_y,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn test_config(padding: f32) -> PackingConfig {
+        PackingConfig {
+            width: 768.,
+            height: 768.,
+            padding,
+            border: padding,
+            packer: Packer::PackedUpwards,
+            sort: SortOrder::HeightDesc,
+            orientation: Orientation::AlwaysPortrait,
+            extrude: 0.,
+            probe_columns: false,
+            try_turns: false,
+            overlap_epsilon: DEFAULT_OVERLAP_EPSILON,
+            tile: None,
+            fixed_rows: None,
+        }
+    }
+
+    fn run_pipeline(config: PackingConfig, cols: i32, rows: i32) -> Vec<Patch> {
+        rand::srand(1234);
+        pack(config, InitialState::new(config, cols, rows, false).patches)
+    }
+
+    // every packer and sort combination
+    fn all_configs(padding: f32) -> Vec<PackingConfig> {
+        let mut configs = Vec::new();
+        let mut packer = Packer::PackedUpwards;
+        loop {
+            let mut sort = SortOrder::HeightDesc;
+            loop {
+                configs.push(PackingConfig {
+                    width: 1024.,
+                    height: 1024.,
+                    packer,
+                    sort,
+                    ..test_config(padding)
+                });
+                sort = sort.cycled();
+                if sort == SortOrder::HeightDesc {
+                    break;
+                }
+            }
+            packer = packer.cycled();
+            if packer == Packer::PackedUpwards {
+                return configs;
+            }
+        }
+    }
+
+    fn sorted_ids(patches: &[Patch]) -> Vec<i32> {
+        let mut ids: Vec<i32> = patches.iter().map(|p| p.id).collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    #[test]
+    fn the_outermost_patches_sit_exactly_border_from_the_atlas_edge() {
+        for border in [1., 10.] {
+            for config in all_configs(4.) {
+                let config = PackingConfig { border, ..config };
+                rand::srand(42);
+                let initial = InitialState::new(test_config(4.), 4, 6, false);
+                let packed = pack(config, initial.patches);
+                let (min, _) = bounding_box(&packed);
+                assert!(
+                    (min.x - border).abs() < 1e-3 && (min.y - border).abs() < 1e-3,
+                    "{:?} {:?} with border {} put the top left patch edges at {:?}",
+                    config.packer,
+                    config.sort,
+                    border,
+                    min
+                );
+                assert!(find_overlaps_within(&packed, 0.).is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn sweeping_widths_fits_a_smaller_atlas_than_the_narrowest() {
+        rand::srand(7);
+        let config = test_config(2.);
+        let patches = InitialState::new(config, 4, 5, false).patches;
+        let fitted = fit_to_area(&patches, config);
+        let packed = pack(fitted, patches.clone());
+        let (_, max) = bounding_box(&packed);
+        assert!(max.x <= fitted.width - config.border + 1e-3);
+        assert!(max.y <= fitted.height - config.border + 1e-3);
+
+        // the narrowest atlas packs tallest, and is the sweep's first candidate
+        let (narrowest, unbounded) = sweep_limits(&patches, &config);
+        let narrow = PackingConfig {
+            width: narrowest.ceil(),
+            height: unbounded,
+            ..config
+        };
+        let height = (bounding_box(&pack(narrow, patches)).1.y + config.border).ceil();
+        assert!(fitted.width * fitted.height <= narrow.width * height);
+        assert!(fitted.width > narrow.width);
+    }
+
+    #[test]
+    fn deterministic_rows_repeat_exactly_in_the_fixed_rows() {
+        let patches: Vec<Patch> = (0..20)
+            .map(|i| sized_patch(i, 20. + (i * 37 % 50) as f32, 10. + (i * 23 % 40) as f32))
+            .collect();
+        for rows in [FixedRows::Count(3), FixedRows::Height(48.)] {
+            let config = PackingConfig {
+                packer: Packer::DeterministicRows,
+                fixed_rows: Some(rows),
+                ..test_config(2.)
+            };
+            let first = pack(config, patches.clone());
+            let second = pack(config, patches.clone());
+            for (a, b) in first.iter().zip(&second) {
+                assert_eq!((a.id, a.center, a.extent), (b.id, b.center, b.extent));
+            }
+            assert!(find_overlaps(&first).is_empty());
+
+            let mut tops: Vec<f32> = first.iter().map(|p| p.top()).collect();
+            tops.sort_by(f32::total_cmp);
+            tops.dedup();
+            match rows {
+                FixedRows::Count(count) => assert_eq!(tops.len(), count),
+                FixedRows::Height(height) => {
+                    for pair in tops.windows(2) {
+                        assert!(pair[1] - pair[0] >= height + config.padding);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn aligned_patches_pack_to_multiples_of_the_alignment() {
+        for config in all_configs(2.) {
+            rand::srand(9);
+            let mut initial = InitialState::new(test_config(2.), 4, 6, false);
+            initial.align(4);
+            for patch in pack(config, initial.patches) {
+                for size in [patch.width(), patch.height()] {
+                    assert_eq!(
+                        size.round() as i32 % 4,
+                        0,
+                        "{:?} {:?} packed patch {} {} wide by {} high",
+                        config.packer,
+                        config.sort,
+                        patch.id,
+                        patch.width(),
+                        patch.height()
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn stats_report_a_fill_ratio_between_zero_and_one() {
+        for config in all_configs(2.) {
+            rand::srand(5);
+            let initial = InitialState::new(test_config(2.), 4, 6, false);
+            let mut walk = PipelineWalk::new(
+                Pipeline::for_config(&config),
+                config,
+                "Initial",
+                initial.patches.clone(),
+            );
+            while walk.advance().is_some() {}
+            let pages = walk_pages(&walk);
+            let stats = json::parse(&export_stats(&pages, &config, &walk.steps)).unwrap();
+
+            let fill = stats
+                .get("fill_ratio")
+                .and_then(json::Value::as_f32)
+                .unwrap();
+            assert!(
+                (0. ..=1.).contains(&fill),
+                "{:?} {:?} filled {}",
+                config.packer,
+                config.sort,
+                fill
+            );
+            if pages.len() == 1 {
+                assert!((fill - fill_ratio(&pages[0].patches)).abs() < 1e-6);
+            }
+            assert_eq!(
+                stats.get("patch_count").and_then(json::Value::as_i64),
+                Some(initial.patches.len() as i64)
+            );
+            let stages = stats.get("stages").and_then(json::Value::as_array).unwrap();
+            assert_eq!(stages.len(), walk.pipeline.stages.len());
+        }
+    }
+
+    #[test]
+    fn toggled_turns_survive_packing() {
+        let config = PackingConfig {
+            packer: Packer::Shelf,
+            ..test_config(2.)
+        };
+        let patches = vec![
+            sized_patch(1, 40., 80.),
+            sized_patch(2, 90., 30.),
+            sized_patch(3, 50., 50.),
+        ];
+        let toggled: HashSet<i32> = vec![1, 2].into_iter().collect();
+        let packed = Pipeline::for_config(&config).keeping_turns().run(
+            with_turns_toggled(patches.clone(), config, &toggled),
+            config,
+        );
+
+        // always portrait would have left the first patch tall and turned the second; toggled,
+        // the first lies on its side and the second keeps its width
+        let extent = |id: i32| packed.iter().find(|p| p.id == id).unwrap().extent;
+        assert_eq!(extent(1), Vec2::new(80., 40.));
+        assert_eq!(extent(2), Vec2::new(90., 30.));
+        assert_eq!(extent(3), Vec2::new(50., 50.));
+        assert!(same_ids(&packed, &patches));
+        assert!(find_overlaps(&packed).is_empty());
+    }
+
+    #[test]
+    fn manifests_size_one_patch_per_entry() {
+        let dir = std::env::temp_dir();
+        let csv = dir.join("texture_packer_manifest_test.csv");
+        let json_path = dir.join("texture_packer_manifest_test.json");
+        std::fs::write(
+            &csv,
+            "name,width,height\nhero.png,32,48\n\"tree, tall.png\",64,128\nrock.png,16,16\n",
+        )
+        .unwrap();
+        std::fs::write(
+            &json_path,
+            "[{ \"name\": \"hero.png\", \"width\": 32, \"height\": 48 }, { \"name\": \"tree, tall.png\", \"width\": 64, \"height\": 128 }, { \"name\": \"rock.png\", \"width\": 16, \"height\": 16 }]",
+        )
+        .unwrap();
+        let from_csv = InitialState::from_manifest(test_config(2.), &csv);
+        let from_json = InitialState::from_manifest(test_config(2.), &json_path);
+        std::fs::remove_file(&csv).ok();
+        std::fs::remove_file(&json_path).ok();
+
+        for initial in [from_csv.unwrap(), from_json.unwrap()] {
+            assert_eq!(initial.patches.len(), 3);
+            let sizes: Vec<(&str, Vec2)> = initial
+                .patches
+                .iter()
+                .map(|p| (initial.names[&p.id].as_str(), p.extent))
+                .collect();
+            assert_eq!(
+                sizes,
+                vec![
+                    ("hero.png", Vec2::new(32., 48.)),
+                    ("tree, tall.png", Vec2::new(64., 128.)),
+                    ("rock.png", Vec2::new(16., 16.)),
+                ]
+            );
+            assert!(find_overlaps(&initial.patches).is_empty());
+        }
+    }
+
+    #[test]
+    fn every_pipeline_step_preserves_patches_and_terminal_layouts_fit() {
+        for config in all_configs(4.) {
+            rand::srand(42);
+            // patches are sized for a smaller atlas so every packer can fit them all on one page
+            let initial = InitialState::new(test_config(4.), 4, 6, false);
+            let mut walk = PipelineWalk::new(
+                Pipeline::for_config(&config),
+                config,
+                "Initial",
+                initial.patches.clone(),
+            );
+
+            while walk.advance().is_some() {
+                let step = walk.current();
+                assert_eq!(
+                    sorted_ids(&step.patches),
+                    sorted_ids(&initial.patches),
+                    "{:?} {:?}: {} lost or duplicated patches",
+                    config.packer,
+                    config.sort,
+                    step.name
+                );
+            }
+
+            let patches = &walk.current().patches;
+            assert!(
+                find_overlaps(patches).is_empty(),
+                "{:?} {:?} left overlaps",
+                config.packer,
+                config.sort
+            );
+            for patch in patches {
+                assert!(
+                    patch.left() >= config.border - 1e-3
+                        && patch.right() <= config.width - config.border + 1e-3
+                        && patch.top() >= config.border - 1e-3
+                        && patch.bottom() <= config.height - config.border + 1e-3,
+                    "{:?} {:?} placed patch {} out of bounds",
+                    config.packer,
+                    config.sort,
+                    patch.id
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn never_orientation_leaves_every_patch_unrotated() {
+        for config in all_configs(4.) {
+            let config = PackingConfig {
+                orientation: Orientation::Never,
+                ..config
+            };
+            rand::srand(3);
+            let mut patches = InitialState::new(test_config(4.), 4, 6, false).patches;
+            // a wide strip of frames mustn't be turned either
+            for patch in patches.iter_mut().take(3) {
+                patch.group = Some(0);
+            }
+            let packed = Pipeline::for_config(&config).run(patches, config);
+            assert!(packed.iter().all(|p| p.rotation == 0.));
+        }
+    }
+
+    #[test]
+    fn overflow_is_detected_once_patches_outgrow_the_atlas() {
+        let config = PackingConfig {
+            width: 256.,
+            height: 256.,
+            ..test_config(4.)
+        };
+        let patches = |count: i32| (0..count).map(|id| sized_patch(id, 60., 60.)).collect();
+
+        // 9 fit comfortably, 30 have more area than the whole atlas
+        assert!(vertical_overflow(&pack(config, patches(9)), config.height).is_none());
+        let packed = pack(config, patches(30));
+        let overflow = vertical_overflow(&packed, config.height).unwrap();
+        assert!(overflow.amount > 0.);
+        for patch in &packed {
+            assert_eq!(
+                overflow.ids.contains(&patch.id),
+                patch.bottom() > config.height
+            );
+        }
+        let lowest = packed.iter().map(|p| p.bottom()).fold(0., f32::max);
+        assert_eq!(overflow.amount, lowest - config.height);
+    }
+
+    #[test]
+    fn extruded_neighbors_keep_their_extrusion_apart() {
+        for config in all_configs(2.) {
+            let config = PackingConfig {
+                extrude: 3.,
+                ..config
+            };
+            rand::srand(11);
+            let initial = InitialState::new(test_config(2.), 4, 6, false);
+            let packed = Pipeline::for_config(&config).run(initial.patches, config);
+
+            for (i, a) in packed.iter().enumerate() {
+                for b in &packed[i + 1..] {
+                    let gap_x = (a.left() - b.right()).max(b.left() - a.right());
+                    let gap_y = (a.top() - b.bottom()).max(b.top() - a.bottom());
+                    assert!(
+                        gap_x.max(gap_y) >= config.extrude * 2. - 1e-3,
+                        "{:?}: patches {} and {} are only {} apart",
+                        config.packer,
+                        a.id,
+                        b.id,
+                        gap_x.max(gap_y)
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn packed_layouts_snap_to_whole_pixels() {
+        for config in all_configs(3.) {
+            rand::srand(5);
+            let initial = InitialState::new(test_config(3.), 4, 6, false);
+            let packed = Pipeline::for_config(&config).run(initial.patches, config);
+
+            assert!(find_overlaps(&packed).is_empty());
+            for patch in &packed {
+                for value in [patch.left(), patch.top(), patch.width(), patch.height()] {
+                    assert_eq!(value.fract(), 0., "{:?}: patch {}", config.packer, patch.id);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn snapping_keeps_flush_neighbors_flush_through_float_noise() {
+        let config = test_config(0.);
+        let left = sized_patch(1, 40., 20.).with_left_and_top(63.9999, 0.);
+        let right = sized_patch(2, 30., 20.).with_left_and_top(left.right(), 0.);
+        let snapped = SnapStage.apply(vec![left, right], config);
+
+        assert_eq!(snapped[0].left(), 64.);
+        assert_eq!(snapped[0].width(), 40.);
+        assert_eq!(snapped[1].left(), 104.);
+        assert_eq!(snapped[1].width(), 30.);
+        assert!(find_overlaps(&snapped).is_empty());
+    }
+
+    #[test]
+    fn bounding_box_spans_packed_patches() {
+        assert_eq!(bounding_box(&[]), (Vec2::ZERO, Vec2::ZERO));
+
+        let config = test_config(2.);
+        let patches = run_pipeline(config, 6, 5);
+        let (min, max) = bounding_box(&patches);
+        assert!(min.x >= 0. && min.y >= 0.);
+        assert!(max.x <= config.width && max.y <= config.height);
+        for patch in &patches {
+            assert!(patch.left() >= min.x && patch.right() <= max.x);
+            assert!(patch.top() >= min.y && patch.bottom() <= max.y);
+        }
+        assert!(patches.iter().any(|p| p.left() == min.x));
+        assert!(patches.iter().any(|p| p.bottom() == max.y));
+    }
+
+    #[cfg(feature = "free-rotation")]
+    fn tilted_patch(center: Vec2, extent: Vec2, degrees: f32) -> Patch {
+        Patch {
+            id: 0,
+            center,
+            extent,
+            rotation: degrees.to_radians(),
+            source_extent: extent,
+            source_rotation: degrees.to_radians(),
+            source_offset: Vec2::ZERO,
+            group: None,
+        }
+    }
+
+    #[cfg(feature = "free-rotation")]
+    #[test]
+    fn rotated_rects_overlap_only_where_their_corners_meet() {
+        let square = Vec2::splat(10.);
+        // a diamond's footprint overlaps an upright square's corner which the diamond leaves empty
+        let diamond = tilted_patch(Vec2::ZERO, square, 45.);
+        let corner = tilted_patch(Vec2::new(12., 12.), square, 0.);
+        assert!(diamond.right() > corner.left() && diamond.bottom() > corner.top());
+        assert!(!diamond.intersects(&corner));
+        assert!(!corner.intersects(&diamond));
+
+        // moved in along the diagonal, the square reaches the diamond's edge
+        let closer = tilted_patch(Vec2::new(8., 8.), square, 0.);
+        assert!(diamond.intersects(&closer));
+
+        // two tilted rects crossing in an X overlap at their centers
+        let a = tilted_patch(Vec2::ZERO, Vec2::new(40., 4.), 30.);
+        let b = tilted_patch(Vec2::ZERO, Vec2::new(40., 4.), -30.);
+        assert!(a.intersects(&b));
+
+        // parallel tilted rects side by side are separated along their shared normal
+        let offset = Vec2::new(-(30f32.to_radians().sin()), 30f32.to_radians().cos()) * 5.;
+        let c = tilted_patch(offset, Vec2::new(40., 4.), 30.);
+        assert!(!a.intersects(&c));
+    }
+
+    fn sized_patch(id: i32, width: f32, height: f32) -> Patch {
+        Patch {
+            id,
+            center: Vec2::ZERO,
+            extent: Vec2::new(width, height),
+            rotation: 0.,
+            source_extent: Vec2::new(width, height),
+            source_rotation: 0.,
+            source_offset: Vec2::ZERO,
+            group: None,
+        }
+    }
+
+    #[test]
+    fn patch_at_finds_the_topmost_patch_under_a_point() {
+        let patches = vec![
+            sized_patch(0, 10., 10.).with_left_and_top(0., 0.),
+            sized_patch(1, 10., 10.).with_left_and_top(10., 0.),
+            sized_patch(2, 10., 10.).with_left_and_top(5., 5.),
+        ];
+        let id_at = |x: f32, y: f32| patch_at(&patches, Vec2::new(x, y)).map(|p| p.id);
+        assert_eq!(id_at(2., 2.), Some(0));
+        assert_eq!(id_at(18., 2.), Some(1));
+        assert_eq!(id_at(7., 7.), Some(2));
+        assert_eq!(id_at(10., 2.), Some(1));
+        assert_eq!(id_at(2., 18.), None);
+    }
+
+    #[test]
+    fn height_sort_breaks_ties_by_width() {
+        let patches = vec![
+            sized_patch(0, 10., 20.),
+            sized_patch(1, 30., 20.),
+            sized_patch(2, 20., 40.),
+            sized_patch(3, 30., 20.),
+            sized_patch(4, 20., 20.),
+        ];
+        let sorted = SortStage {
+            order: SortOrder::HeightDesc,
+        }
+        .apply(patches, test_config(2.));
+        let ids: Vec<i32> = sorted.iter().map(|p| p.id).collect();
+        assert_eq!(ids, vec![2, 1, 3, 4, 0]);
+    }
+
+    #[test]
+    fn nan_extents_sort_last_without_panicking() {
+        let patches = vec![
+            sized_patch(0, 10., f32::NAN),
+            sized_patch(1, 30., 20.),
+            sized_patch(2, f32::NAN, 40.),
+            sized_patch(3, 20., 10.),
+        ];
+        let ids = |order: SortOrder| {
+            let sorted = SortStage { order }.apply(patches.clone(), test_config(2.));
+            sorted.iter().map(|p| p.id).collect::<Vec<i32>>()
+        };
+
+        // a NaN width doesn't matter to the height key, only to the width tie break
+        assert_eq!(ids(SortOrder::HeightDesc), vec![2, 1, 3, 0]);
+        assert_eq!(ids(SortOrder::HeightAsc), vec![3, 1, 2, 0]);
+        assert_eq!(ids(SortOrder::AreaDesc), vec![1, 3, 0, 2]);
+        assert_eq!(ids(SortOrder::AreaAsc), vec![3, 1, 0, 2]);
+    }
+
+    #[test]
+    fn ascending_orders_reverse_descending_ones() {
+        // distinct heights, widths and areas, so no tie breaks come into it
+        let patches: Vec<Patch> = (0..8)
+            .map(|id| sized_patch(id, (10 + id * 7 % 8) as f32, (10 + id * 5 % 8) as f32 + 0.5))
+            .collect();
+        let ids = |order: SortOrder| {
+            let sorted = SortStage { order }.apply(patches.clone(), test_config(2.));
+            sorted.iter().map(|p| p.id).collect::<Vec<i32>>()
+        };
+
+        for &(descending, ascending) in [
+            (SortOrder::HeightDesc, SortOrder::HeightAsc),
+            (SortOrder::AreaDesc, SortOrder::AreaAsc),
+        ]
+        .iter()
+        {
+            let mut reversed = ids(descending);
+            reversed.reverse();
+            assert_eq!(ids(ascending), reversed);
+            assert_ne!(ids(ascending), ids(descending));
+        }
+    }
+
+    #[test]
+    fn spatial_grid_finds_the_same_intersections_as_a_full_scan() {
+        rand::srand(99);
+        let config = test_config(3.);
+        let random_patch = |id: i32| {
+            let size = Vec2::new(rand::gen_range(4., 80.), rand::gen_range(4., 80.));
+            Patch {
+                center: Vec2::new(
+                    rand::gen_range(0., config.width),
+                    rand::gen_range(0., config.height),
+                ),
+                ..sized_patch(id, size.x, size.y)
+            }
+        };
+        let placed: Vec<Patch> = (0..300).map(random_patch).collect();
+        for cell_size in [5., 40., 500.] {
+            let mut grid = SpatialGrid::new(cell_size);
+            for patch in &placed {
+                grid.insert(*patch);
+            }
+            for _ in 0..200 {
+                let test = random_patch(-1);
+                let naive: Vec<i32> = placed
+                    .iter()
+                    .filter(|p| PackUpwardsStage::blocks(&test, p, DEFAULT_OVERLAP_EPSILON))
+                    .map(|p| p.id)
+                    .collect();
+                let found: Vec<i32> =
+                    PackUpwardsStage::find_intersections(test, &grid, DEFAULT_OVERLAP_EPSILON)
+                        .iter()
+                        .map(|p| p.id)
+                        .collect();
+                assert_eq!(found, naive);
+            }
+        }
+    }
+
+    // an L of solid cells down the left column and along the bottom row, or turned a half turn,
+    // along the top row and down the right column
+    #[cfg(feature = "alpha-mask")]
+    fn l_mask(half_turned: bool) -> OccupancyMask {
+        let alpha: Vec<u8> = (0..16)
+            .map(|i| {
+                let (x, y) = (i % 4, i / 4);
+                let solid = if half_turned {
+                    y == 0 || x == 3
+                } else {
+                    x == 0 || y == 3
+                };
+                if solid {
+                    255
+                } else {
+                    0
+                }
+            })
+            .collect();
+        OccupancyMask::from_alpha(4, 4, &alpha, 1)
+    }
+
+    #[cfg(feature = "alpha-mask")]
+    #[test]
+    fn l_shaped_masks_interlock() {
+        let (a_mask, b_mask) = (l_mask(false), l_mask(true));
+        let a = Patch {
+            center: Vec2::new(2., 2.),
+            ..sized_patch(0, 4., 4.)
+        };
+        // tucked up and right by one cell, b's footprint covers most of a's, but only where a
+        // is transparent
+        let b = Patch {
+            center: Vec2::new(3., 1.),
+            ..sized_patch(1, 4., 4.)
+        };
+        assert!(a.intersects(&b));
+        assert!(!masks_intersect(&a, Some(&a_mask), &b, Some(&b_mask), 0.));
+        // without masks, or with b moved squarely over a, they collide
+        assert!(masks_intersect(&a, None, &b, None, 0.));
+        let stacked = b.with_left_and_top(0., 0.);
+        assert!(masks_intersect(
+            &a,
+            Some(&a_mask),
+            &stacked,
+            Some(&b_mask),
+            0.
+        ));
+
+        // packing the two nests them rather than setting them side by side
+        let mut masks = HashMap::new();
+        masks.insert(0, a_mask);
+        masks.insert(1, b_mask);
+        let stage = MaskPackStage { masks };
+        let packed = stage.apply(
+            vec![sized_patch(0, 4., 4.), sized_patch(1, 4., 4.)],
+            PackingConfig {
+                width: 64.,
+                height: 64.,
+                ..test_config(0.)
+            },
+        );
+        assert!(packed[0].intersects(&packed[1]));
+        assert!(!masks_intersect(
+            &packed[0],
+            stage.masks.get(&0),
+            &packed[1],
+            stage.masks.get(&1),
+            0.
+        ));
+    }
+
+    #[test]
+    fn probing_columns_never_packs_taller() {
+        let height = |patches: &[Patch]| patches.iter().map(|p| p.bottom()).fold(0., f32::max);
+        for seed in 0..8 {
+            for padding in [0., 4.] {
+                let config = test_config(padding);
+                let layout = |stages: &str| {
+                    rand::srand(seed);
+                    let patches = InitialState::new(config, 6, 8, false).patches;
+                    Pipeline::from_names(stages).unwrap().run(patches, config)
+                };
+                let single = layout("upright,sort-height,flow,pack-upwards");
+                let probed = layout("upright,sort-height,flow,pack-upwards-probe");
+                // flush stacking at zero padding can overlap by a rounding error, as it does
+                // in a single column
+                if padding > 0. {
+                    assert!(find_overlaps(&probed).is_empty());
+                }
+                assert!(
+                    height(&probed) <= height(&single),
+                    "seed {}: probing packed {} tall vs {}",
+                    seed,
+                    height(&probed),
+                    height(&single)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn turning_while_packing_upwards_never_packs_taller() {
+        let height = |patches: &[Patch]| patches.iter().map(|p| p.bottom()).fold(0., f32::max);
+        let mut turned_any = false;
+        for seed in 0..8 {
+            let config = test_config(4.);
+            let layout = |stages: &str| {
+                rand::srand(seed);
+                let patches = InitialState::new(config, 6, 8, false).patches;
+                Pipeline::from_names(stages).unwrap().run(patches, config)
+            };
+            let upright = layout("upright,sort-height,flow,pack-upwards");
+            let turning = layout("upright,sort-height,flow,pack-upwards-turn");
+            assert!(find_overlaps(&turning).is_empty());
+            assert!(turning.iter().all(|p| {
+                p.left() >= config.padding - 1e-3
+                    && p.right() <= config.width - config.padding + 1e-3
+            }));
+            assert!(
+                height(&turning) <= height(&upright),
+                "seed {}: turning packed {} tall vs {}",
+                seed,
+                height(&turning),
+                height(&upright)
+            );
+            turned_any |= turning.iter().any(is_turned);
+        }
+        assert!(turned_any);
+
+        // engines which can't turn sprites back never see one turned
+        let config = PackingConfig {
+            orientation: Orientation::Never,
+            try_turns: true,
+            ..test_config(4.)
+        };
+        rand::srand(0);
+        let packed = pack(config, InitialState::new(config, 6, 8, false).patches);
+        assert!(!packed.iter().any(is_turned));
+    }
+
+    #[test]
+    fn compacting_left_only_slides_patches_left() {
+        for seed in 0..4 {
+            let config = test_config(4.);
+            let layout = |stages: &str| {
+                rand::srand(seed);
+                let patches = InitialState::new(config, 6, 8, false).patches;
+                Pipeline::from_names(stages).unwrap().run(patches, config)
+            };
+            let packed = layout("upright,sort-height,flow,pack-upwards");
+            let compacted = layout("upright,sort-height,flow,pack-upwards,compact-left");
+            assert!(find_overlaps(&compacted).is_empty());
+            for (before, after) in packed.iter().zip(&compacted) {
+                assert_eq!(before.id, after.id);
+                assert_eq!(before.top(), after.top());
+                assert!(after.left() <= before.left());
+                assert!(after.left() >= config.padding);
+            }
+
+            // packing upwards again after compacting mustn't collide with the shifted patches
+            let alternated = layout(
+                "upright,sort-height,flow,pack-upwards,compact-left,pack-upwards,compact-left",
+            );
+            assert!(find_overlaps(&alternated).is_empty());
+            let bottom = |patches: &[Patch]| bounding_box(patches).1.y;
+            assert!(bottom(&alternated) <= bottom(&packed));
+        }
+    }
+
+    #[test]
+    fn best_fit_flow_fills_more_than_snaking_flow() {
+        let config = test_config(2.);
+        let layout = |stages: &str| {
+            rand::srand(1234);
+            let patches = InitialState::new(config, 12, 10, false).patches;
+            Pipeline::from_names(stages).unwrap().run(patches, config)
+        };
+
+        let snaking = layout("upright,sort-height,flow");
+        let best_fit = layout("upright,sort-height,flow-best-fit");
+        assert!(find_overlaps(&best_fit).is_empty());
+        assert!(
+            fill_ratio(&best_fit) > fill_ratio(&snaking),
+            "best fit {} vs snaking {}",
+            fill_ratio(&best_fit),
+            fill_ratio(&snaking)
+        );
+    }
+
+    #[test]
+    fn first_fit_shelves_pack_at_least_as_dense_as_next_fit() {
+        let next_fit = run_pipeline(
+            PackingConfig {
+                packer: Packer::Shelf,
+                ..test_config(4.)
+            },
+            5,
+            8,
+        );
+        let first_fit = run_pipeline(
+            PackingConfig {
+                packer: Packer::ShelfFirstFit,
+                ..test_config(4.)
+            },
+            5,
+            8,
+        );
+
+        assert!(fill_ratio(&first_fit) >= fill_ratio(&next_fit));
+    }
+
+    #[test]
+    fn unity_rects_count_up_from_the_bottom_of_the_page() {
+        let config = PackingConfig {
+            width: 128.,
+            height: 256.,
+            ..test_config(2.)
+        };
+        let upright = sized_patch(0, 30., 40.).with_left_and_top(10., 20.);
+        let rotated = Patch {
+            extent: Vec2::new(40., 30.),
+            rotation: std::f32::consts::FRAC_PI_2,
+            ..sized_patch(1, 30., 40.)
+        }
+        .with_left_and_top(50., 200.);
+        let names = vec![(0, "hero.png".to_string())].into_iter().collect();
+        let sheet = json::parse(&export_unity(
+            &[upright, rotated],
+            &config,
+            &names,
+            "atlas.png",
+        ))
+        .unwrap();
+        assert_eq!(
+            sheet.get("texture").and_then(json::Value::as_str),
+            Some("atlas.png")
+        );
+
+        let sprites = sheet
+            .get("sprites")
+            .and_then(json::Value::as_array)
+            .unwrap();
+        let rect = |sprite: &json::Value| {
+            let rect = sprite.get("rect").unwrap();
+            ["x", "y", "width", "height"].map(|key| rect.get(key).unwrap().as_i64().unwrap())
+        };
+        // 256 tall, so the bottom at 60 down from the top is 196 up from the bottom
+        assert_eq!(sprites[0].get("name").unwrap().as_str(), Some("hero"));
+        assert_eq!(rect(&sprites[0]), [10, 196, 30, 40]);
+        assert_eq!(sprites[0].get("rotated").unwrap().as_bool(), Some(false));
+        // turned, it takes up 40 x 30 from 200 to 230 down, so 26 up
+        assert_eq!(sprites[1].get("name").unwrap().as_str(), Some("sprite_1"));
+        assert_eq!(rect(&sprites[1]), [50, 26, 40, 30]);
+        assert_eq!(sprites[1].get("rotated").unwrap().as_bool(), Some(true));
+        let pivot = sprites[1].get("pivot").unwrap();
+        assert_eq!(pivot.get("x").unwrap().as_f32(), Some(0.5));
+        assert_eq!(pivot.get("y").unwrap().as_f32(), Some(0.5));
+    }
+
+    #[test]
+    fn libgdx_atlas_regions_match_packed_patches() {
+        let config = PackingConfig {
+            packer: Packer::MaxRects,
+            ..test_config(2.)
+        };
+        let patches = run_pipeline(config, 3, 6);
+        assert!(patches.iter().any(|p| quarter_turns(p.rotation) % 2 != 0));
+
+        let atlas = export_libgdx_atlas(&patches, &config, &HashMap::new(), "atlas.png");
+        let mut lines = atlas
+            .lines()
+            .skip_while(|line| !line.starts_with("repeat:"));
+        lines.next();
+
+        let mut regions = Vec::new();
+        while let Some(name) = lines.next() {
+            let mut fields = HashMap::new();
+            for _ in 0..6 {
+                let (key, value) = lines.next().unwrap().trim().split_once(": ").unwrap();
+                fields.insert(key, value.to_string());
+            }
+            let pair = |key: &str| -> (i32, i32) {
+                let (a, b) = fields[key].split_once(", ").unwrap();
+                (a.parse().unwrap(), b.parse().unwrap())
+            };
+            let (x, y) = pair("xy");
+            let (width, height) = pair("size");
+            // rotated regions occupy their size turned on its side
+            let (width, height) = if fields["rotate"] == "true" {
+                (height, width)
+            } else {
+                (width, height)
+            };
+            regions.push((name.to_string(), x, y, width, height));
+        }
+
+        assert_eq!(regions.len(), patches.len());
+        for (patch, region) in patches.iter().zip(&regions) {
+            assert_eq!(
+                region,
+                &(
+                    format!("sprite_{}", patch.id),
+                    patch.left().round() as i32,
+                    patch.top().round() as i32,
+                    patch.width().round() as i32,
+                    patch.height().round() as i32
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn imported_json_matches_exported_layout() {
+        let config = test_config(4.);
+        let packed = run_pipeline(config, 4, 5);
+        let page = Page {
+            width: config.width,
+            height: config.height,
+            patches: packed.clone(),
+        };
+        let imported =
+            import_atlas_json(&export_atlas_json(&[page], &config, &HashMap::new())).unwrap();
+        assert!(imported.problems.is_empty());
+        assert_eq!(
+            (imported.width, imported.height),
+            (config.width, config.height)
+        );
+        assert_eq!(imported.patches.len(), packed.len());
+        for (a, b) in packed.iter().zip(&imported.patches) {
+            assert_eq!(a.id, b.id);
+            assert_eq!((a.left(), a.top()), (b.left(), b.top()));
+            assert_eq!((a.width(), a.height()), (b.width(), b.height()));
+            assert_eq!(a.rotation != 0., b.rotation != 0.);
+        }
+
+        // bad frames are left out and reported, overlapping ones are kept for find_overlaps
+        let malformed = r#"{ "atlas": { "width": 64, "height": 64 }, "frames": [
+            { "id": 0, "x": 0, "y": 0, "width": 10, "height": 10 },
+            { "id": 1, "x": 5, "y": 5, "width": 10, "height": 10 },
+            { "id": 2, "x": "left", "y": 0, "width": 10, "height": 10 },
+            { "id": 1, "x": 40, "y": 40, "width": 10, "height": 10 }
+        ] }"#;
+        let imported = import_atlas_json(malformed).unwrap();
+        assert_eq!(imported.patches.len(), 2);
+        assert_eq!(imported.problems.len(), 2);
+        assert_eq!(find_overlaps(&imported.patches), vec![(0, 1)]);
+        assert!(import_atlas_json("[1, 2]").is_err());
+    }
+
+    #[test]
+    fn versioned_atlas_json_round_trips() {
+        let config = test_config(4.);
+        let page = Page {
+            width: config.width,
+            height: config.height,
+            patches: run_pipeline(config, 3, 3),
+        };
+        let exported = export_atlas_json(&[page], &config, &HashMap::new());
+        let document = json::parse(&exported).unwrap();
+        assert_eq!(
+            document.get("version").and_then(json::Value::as_i64),
+            Some(ATLAS_JSON_VERSION)
+        );
+        let imported = import_atlas_json(&exported).unwrap();
+        assert!(imported.problems.is_empty());
+        assert_eq!(imported.patches.len(), 9);
+
+        let bumped = exported.replacen(
+            &format!("\"version\": {}", ATLAS_JSON_VERSION),
+            &format!("\"version\": {}", ATLAS_JSON_VERSION + 1),
+            1,
+        );
+        let error = import_atlas_json(&bumped).err().unwrap();
+        assert!(error.contains("unsupported atlas version"), "{}", error);
+    }
+
+    #[test]
+    fn stable_ids_depend_only_on_the_name() {
+        let id = Patch::stable_id_from_name("hero_idle_01.png");
+        assert_eq!(id, Patch::stable_id_from_name("hero_idle_01.png"));
+        assert_ne!(id, Patch::stable_id_from_name("hero_idle_02.png"));
+        for name in ["", "a.png", "\u{1F600}.png"] {
+            assert!(Patch::stable_id_from_name(name) >= 0);
+        }
+    }
+
+    #[test]
+    fn grouped_frames_pack_edge_to_edge() {
+        // whole pixel sizes, so snapping leaves members exactly flush
+        let mut patches: Vec<Patch> = (0..12)
+            .map(|id| sized_patch(id, (20 + id * 7 % 30) as f32, (15 + id * 11 % 40) as f32))
+            .collect();
+        let grouped = [(1, 2), (3, 1), (5, 1), (7, 1), (10, 2)];
+        for &(id, group) in grouped.iter() {
+            patches[id].group = Some(group);
+        }
+
+        for config in all_configs(2.) {
+            let packed = pack(config, patches.clone());
+            assert!(find_overlaps(&packed).is_empty());
+            for group in 1..=2 {
+                // members keep the order they were listed in, end to end along the strip
+                let members: Vec<&Patch> = grouped
+                    .iter()
+                    .filter(|&&(_, g)| g == group)
+                    .map(|&(id, _)| packed.iter().find(|p| p.id == id as i32).unwrap())
+                    .collect();
+                for pair in members.windows(2) {
+                    let (a, b) = (pair[0], pair[1]);
+                    let side_by_side = a.right() == b.left() && a.top() == b.top();
+                    let stacked = a.bottom() == b.top() && a.left() == b.left();
+                    assert!(
+                        side_by_side || stacked,
+                        "{:?}: {} and {} aren't adjacent",
+                        config.packer,
+                        a.id,
+                        b.id
+                    );
+                    assert_eq!(a.rotation, b.rotation);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn source_dimensions_survive_packing() {
+        rand::srand(7);
+        let config = test_config(4.);
+        let initial = InitialState::new(config, 3, 6, false);
+        let packed = Pipeline::for_config(&config).run(initial.patches.clone(), config);
+
+        assert!(packed.iter().any(|p| p.rotation != 0.));
+        for patch in &packed {
+            let original = initial.patches.iter().find(|p| p.id == patch.id).unwrap();
+            assert_eq!(patch.source_extent, original.source_extent);
+            assert_eq!(patch.source_rotation, original.source_rotation);
+        }
+    }
+
+    #[test]
+    fn skyline_packs_without_overlaps() {
+        let config = PackingConfig {
+            packer: Packer::Skyline,
+            ..test_config(4.)
+        };
+        let patches = run_pipeline(config, 4, 8);
+
+        assert!(find_overlaps(&patches).is_empty());
+        assert!(patches
+            .iter()
+            .all(|p| p.left() >= config.padding && p.right() <= config.width - config.padding));
+    }
+
+    #[test]
+    fn flow_keeps_patches_inside_right_padding() {
+        let config = test_config(8.);
+        for seed in 0..20 {
+            rand::srand(seed);
+            let patches = Pipeline::from_names("upright,sort-height,flow")
+                .unwrap()
+                .run(InitialState::new(config, 4, 6, false).patches, config);
+
+            for patch in &patches {
+                assert!(
+                    patch.right() <= config.width - config.padding + 1e-3,
+                    "seed {}: patch {} ends at {}",
+                    seed,
+                    patch.id,
+                    patch.right()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn zero_padding_packs_patches_flush() {
+        let patches = run_pipeline(test_config(0.), 3, 6);
+
+        // centers round-trip through f32, so touching edges may be off by a hair
+        for (i, a) in patches.iter().enumerate() {
+            for b in &patches[i + 1..] {
+                let overlap_x = a.right().min(b.right()) - a.left().max(b.left());
+                let overlap_y = a.bottom().min(b.bottom()) - a.top().max(b.top());
+                assert!(
+                    overlap_x < 1e-3 || overlap_y < 1e-3,
+                    "patches {} and {} overlap",
+                    a.id,
+                    b.id
+                );
+            }
+        }
+        for patch in &patches {
+            // every patch rests either on the atlas top or directly on a patch above it
+            let resting_on_patch = patches.iter().any(|other| {
+                other.id != patch.id
+                    && (other.bottom() - patch.top()).abs() < 1e-3
+                    && other.left() < patch.right()
+                    && other.right() > patch.left()
+            });
+            assert!(
+                patch.top().abs() < 1e-3 || resting_on_patch,
+                "patch {} has a gap above it",
+                patch.id
+            );
+        }
+    }
+
+    #[test]
+    fn shuffling_is_repeatable_per_seed() {
+        let ids = |seed: u64| {
+            rand::srand(seed);
+            sorted_ids(&InitialState::new(test_config(2.), 6, 5, true).patches)
+        };
+        let order = |seed: u64| {
+            rand::srand(seed);
+            InitialState::new(test_config(2.), 6, 5, true)
+                .patches
+                .iter()
+                .map(|p| p.id)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(order(17), order(17));
+        assert_ne!(order(17), order(18));
+        // shuffling only reorders, every patch is still there
+        assert_eq!(ids(17), (0..30).collect::<Vec<_>>());
+
+        rand::srand(17);
+        let unshuffled = InitialState::new(test_config(2.), 6, 5, false).patches;
+        assert_eq!(
+            unshuffled.iter().map(|p| p.id).collect::<Vec<_>>(),
+            (0..30).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn uniform_tiles_fill_a_grid_and_export_their_cells() {
+        rand::srand(3);
+        let config = PackingConfig {
+            width: 100.,
+            tile: Some(Vec2::new(20., 10.)),
+            ..test_config(2.)
+        };
+        let patches = pack(config, InitialState::new(config, 3, 3, false).patches);
+
+        // (100 - 2) / (20 + 2) leaves room for four tiles a row
+        assert_eq!(patches.len(), 9);
+        for (i, patch) in patches.iter().enumerate() {
+            assert_eq!(patch.extent, Vec2::new(20., 10.));
+            let (row, col) = (i as i32 / 4, i as i32 % 4);
+            assert_eq!(tile_cell(patch, &config), Some((row, col)));
+            assert_eq!(
+                (patch.left(), patch.top()),
+                (2. + 22. * col as f32, 2. + 12. * row as f32)
+            );
+        }
+        assert!(find_overlaps(&patches).is_empty());
+
+        let json = export_atlas_json(
+            &[Page {
+                width: config.width,
+                height: config.height,
+                patches,
+            }],
+            &config,
+            &HashMap::new(),
+        );
+        assert!(json.contains("\"row\": 2, \"col\": 0"));
+    }
+
+    #[test]
+    fn fitting_to_an_aspect_ratio_lands_near_it() {
+        for packer in [Packer::PackedUpwards, Packer::MaxRects, Packer::Skyline] {
+            for aspect in [0.5, 1., 2.] {
+                rand::srand(5);
+                let config = PackingConfig {
+                    packer,
+                    ..test_config(2.)
+                };
+                let patches = InitialState::new(config, 6, 6, false).patches;
+                let pipeline = Pipeline::for_config(&config);
+
+                let fitted = fit_to_aspect(&patches, config, &pipeline, aspect);
+                let packed = pipeline.run(patches, fitted);
+                assert!(
+                    ((fitted.width / fitted.height) / aspect - 1.).abs() <= ASPECT_TOLERANCE,
+                    "{}: fit {} x {} for aspect {}",
+                    packer.name(),
+                    fitted.width,
+                    fitted.height,
+                    aspect
+                );
+                let (min, max) = bounding_box(&packed);
+                assert!(min.x >= 0. && min.y >= 0.);
+                assert!(max.x <= fitted.width && max.y <= fitted.height);
+            }
+        }
+    }
+
+    #[test]
+    fn edges_within_the_overlap_epsilon_only_touch() {
+        let at = |id: i32, left: f32| sized_patch(id, 10., 10.).with_left_and_top(left, 0.);
+
+        // exactly flush
+        let patches = [at(0, 0.), at(1, 10.)];
+        assert!(find_overlaps_within(&patches, 0.).is_empty());
+        assert!(find_overlaps(&patches).is_empty());
+
+        // flush but for float rounding
+        let patches = [at(0, 0.), at(1, 10. - 1e-4)];
+        assert_eq!(find_overlaps_within(&patches, 0.), vec![(0, 1)]);
+        assert!(find_overlaps(&patches).is_empty());
+
+        // a whole pixel over is a real overlap
+        let patches = [at(0, 0.), at(1, 9.)];
+        assert_eq!(find_overlaps(&patches), vec![(0, 1)]);
+        assert_eq!(find_overlaps_within(&patches, 0.5), vec![(0, 1)]);
+        assert!(find_overlaps_within(&patches, 1.).is_empty());
+    }
+
+    #[test]
+    fn stress_patterns_are_repeatable_and_pack_cleanly() {
+        for pattern in [
+            StressPattern::Squares,
+            StressPattern::Mixed,
+            StressPattern::PowersOfTwo,
+        ] {
+            let config = test_config(2.);
+            let patches = InitialState::stress(config, pattern, 120).patches;
+            assert_eq!(patches.len(), 120);
+            assert_eq!(sorted_ids(&patches), (0..120).collect::<Vec<_>>());
+            let again = InitialState::stress(config, pattern, 120).patches;
+            assert!(patches
+                .iter()
+                .zip(&again)
+                .all(|(a, b)| a.center == b.center && a.extent == b.extent));
+
+            for config in all_configs(2.) {
+                let packed = pack(config, patches.clone());
+                assert_eq!(packed.len(), patches.len());
+                assert!(
+                    find_overlaps(&packed).is_empty(),
+                    "{:?} packed with overlaps by {}",
+                    pattern,
+                    config.packer.name()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn trimming_pages_leaves_only_padding_around_the_patches() {
+        for extrude in [0., 2.] {
+            rand::srand(8);
+            let config = PackingConfig {
+                extrude,
+                ..test_config(3.)
+            };
+            let patches = pack(config, InitialState::new(config, 3, 3, false).patches);
+            let pipeline = Pipeline::for_config(&config);
+            let pages = trim_pages(&paginate(patches, config, &pipeline), &config);
+
+            let page = &pages[0];
+            let extruded: Vec<Patch> = page.patches.iter().map(|p| p.extruded(extrude)).collect();
+            let (min, max) = bounding_box(&extruded);
+            assert!((min.x - config.padding).abs() < 1e-3);
+            assert!((min.y - config.padding).abs() < 1e-3);
+            assert!((page.width - (max.x + config.padding)).abs() < 1e-3);
+            assert!((page.height - (max.y + config.padding)).abs() < 1e-3);
+            assert!(page.height < config.height);
+
+            let json = export_atlas_json(&pages, &config, &HashMap::new());
+            assert!(json.contains(&format!(
+                "\"width\": {}, \"height\": {}",
+                page.width.round() as i32,
+                page.height.round() as i32
+            )));
+        }
+    }
+
+    #[test]
+    fn patches_too_big_for_one_page_fill_two() {
+        for packer in [
+            Packer::PackedUpwards,
+            Packer::MaxRects,
+            Packer::Shelf,
+            Packer::Guillotine,
+            Packer::Skyline,
+        ] {
+            let config = PackingConfig {
+                width: 256.,
+                height: 256.,
+                packer,
+                ..test_config(0.)
+            };
+            // three half-page columns: more than one page's area, two pages' worth of room
+            let patches: Vec<Patch> = (0..3).map(|id| sized_patch(id, 128., 256.)).collect();
+            let pipeline = Pipeline::for_config(&config);
+            let pages = paginate(pipeline.run(patches, config), config, &pipeline);
+
+            assert_eq!(pages.len(), 2, "{}", packer.name());
+            assert_eq!(pages[0].patches.len(), 2, "{}", packer.name());
+            for page in &pages {
+                assert!(page.patches.iter().all(|p| page.contains(p)));
+                assert!(find_overlaps(&page.patches).is_empty());
+            }
+
+            let json = export_atlas_json(&pages, &config, &HashMap::new());
+            let document = json::parse(&json).unwrap();
+            let images: Vec<&str> = document
+                .get("pages")
+                .and_then(json::Value::as_array)
+                .unwrap()
+                .iter()
+                .filter_map(|page| page.get("image").and_then(json::Value::as_str))
+                .collect();
+            assert_eq!(images, vec!["page_0.png", "page_1.png"]);
+        }
+    }
+
+    #[test]
+    fn scaled_oversized_patches_fit_with_their_extrusion_and_keep_their_trim() {
+        let config = PackingConfig {
+            extrude: 2.,
+            ..test_config(4.)
+        };
+        let too_wide = Patch {
+            source_extent: Vec2::new(1100., 120.),
+            source_offset: Vec2::new(50., 10.),
+            ..sized_patch(1, 1000., 100.)
+        };
+        // fits between the borders, but not once extruded
+        let snug = sized_patch(2, config.width - 2. * config.border, 50.);
+        let mut initial = InitialState {
+            patches: vec![too_wide, snug, sized_patch(3, 20., 20.)],
+            names: HashMap::new(),
+            #[cfg(feature = "alpha-mask")]
+            masks: HashMap::new(),
+        };
+        let oversized = initial
+            .fit_oversized(&config, OversizedPolicy::Scale)
+            .unwrap();
+        assert_eq!(oversized, vec![1, 2]);
+
+        let scaled = initial.patches[0];
+        let scale = scaled.extent.x / 1000.;
+        assert!((scaled.source_extent - Vec2::new(1100., 120.) * scale).length() < 1e-3);
+        assert!((scaled.source_offset - Vec2::new(50., 10.) * scale).length() < 1e-3);
+
+        let packed = pack(config, initial.patches);
+        for patch in packed.iter().map(|p| p.extruded(config.extrude)) {
+            assert!(patch.left() >= config.border - 1e-3);
+            assert!(patch.right() <= config.width - config.border + 1e-3);
+        }
+    }
+
+    #[test]
+    fn csv_rows_describe_each_patch() {
+        let patch = sized_patch(7, 30.4, 12.).with_left_and_top(2., 5.6);
+        let turned = Patch {
+            rotation: std::f32::consts::FRAC_PI_2,
+            ..sized_patch(8, 10., 20.).with_left_and_top(40., 2.)
+        };
+        // a half turn leaves the patch's footprint as it was, so like the other formats it isn't
+        // reported as rotated
+        let upside_down = Patch {
+            rotation: std::f32::consts::PI,
+            ..sized_patch(9, 8., 8.).with_left_and_top(2., 40.)
+        };
+        let page = Page {
+            width: 64.,
+            height: 64.,
+            patches: vec![patch, turned, upside_down],
+        };
+        let mut names = HashMap::new();
+        names.insert(7, "hero, idle.png".to_string());
+
+        let csv = export_atlas_csv(&[page], &names);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "id,name,x,y,width,height,rotated,page");
+        assert_eq!(lines[1], "7,\"hero, idle.png\",2,6,30,12,false,0");
+        assert_eq!(lines[2], "8,,40,2,10,20,true,0");
+        assert_eq!(lines[3], "9,,2,40,8,8,false,0");
+        assert_eq!(lines.len(), 4);
+    }
+
+    #[test]
+    fn jumping_to_a_step_matches_stepping_there() {
+        rand::srand(4);
+        let config = test_config(2.);
+        let patches = InitialState::new(config, 3, 3, false).patches;
+        let walk = || {
+            PipelineWalk::new(
+                Pipeline::for_config(&config),
+                config,
+                "Initial",
+                patches.clone(),
+            )
+        };
+        let positions = |patches: &[Patch]| patches.iter().map(|p| p.center).collect::<Vec<_>>();
+
+        let mut stepped = walk();
+        stepped.advance();
+        stepped.advance();
+        stepped.advance();
+
+        let mut jumped = walk();
+        assert!(jumped.jump_to(0).is_none());
+        assert_eq!(
+            jumped.jump_to(3).map(|from| positions(&from)),
+            Some(positions(&patches))
+        );
+        assert_eq!(jumped.steps.len(), 4);
+        assert_eq!(jumped.current().name, stepped.current().name);
+        assert_eq!(
+            positions(&jumped.current().patches),
+            positions(&stepped.current().patches)
+        );
+
+        assert!(jumped.jump_to(1).is_some());
+        assert_eq!(jumped.steps.len(), 2);
+        let past_the_end = jumped.pipeline.stages.len() + 1;
+        assert!(jumped.jump_to(past_the_end).is_none());
+    }
+
+    #[test]
+    fn random_counts_survive_the_pipeline() {
+        for count in [1, 2, 47] {
+            rand::srand(count as u64);
+            let patches = InitialState::random(test_config(2.), count).patches;
+            assert_eq!(patches.len(), count);
+            for config in all_configs(2.) {
+                let packed = pack(config, patches.clone());
+                assert_eq!(sorted_ids(&packed), (0..count as i32).collect::<Vec<_>>());
+            }
+        }
+    }
+}
@@ -0,0 +1,73 @@
+// Diagnostic output on stderr, for following what a run did without a window. Silent until
+// `set_verbosity` raises it, so interactive use stays quiet; main raises it once per --verbose.
+
+use std::sync::atomic::{AtomicU8, Ordering};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Level {
+    Warn,
+    Info,
+    Trace,
+}
+
+impl Level {
+    pub fn name(self) -> &'static str {
+        match self {
+            Level::Warn => "warn",
+            Level::Info => "info",
+            Level::Trace => "trace",
+        }
+    }
+
+    // the verbosity at which messages of this level start showing; warnings come with the
+    // summary of what happened, while tracing every step takes a second --verbose
+    fn verbosity(self) -> u8 {
+        match self {
+            Level::Warn | Level::Info => 1,
+            Level::Trace => 2,
+        }
+    }
+}
+
+static VERBOSITY: AtomicU8 = AtomicU8::new(0);
+
+pub fn set_verbosity(verbosity: u8) {
+    VERBOSITY.store(verbosity, Ordering::Relaxed);
+}
+
+pub fn enabled(level: Level) -> bool {
+    VERBOSITY.load(Ordering::Relaxed) >= level.verbosity()
+}
+
+// writes `message` to stderr if `level` is enabled; use the log_ macros rather than calling
+// this, so messages which won't show aren't formatted
+pub fn write(level: Level, message: std::fmt::Arguments) {
+    eprintln!("[{}] {}", level.name(), message);
+}
+
+#[macro_export]
+macro_rules! log_warn {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled($crate::log::Level::Warn) {
+            $crate::log::write($crate::log::Level::Warn, format_args!($($arg)*));
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! log_info {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled($crate::log::Level::Info) {
+            $crate::log::write($crate::log::Level::Info, format_args!($($arg)*));
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! log_trace {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled($crate::log::Level::Trace) {
+            $crate::log::write($crate::log::Level::Trace, format_args!($($arg)*));
+        }
+    };
+}
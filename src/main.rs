@@ -1,441 +1,3089 @@
 use macroquad::prelude::*;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+use texturepacker::*;
 
 /////////////////////////////////////////////////////////////////////////////////
 
-#[derive(Copy, Clone, Debug)]
-struct Patch {
-    id: i32,
-    center: Vec2,
-    extent: Vec2,
-    rotation: f32,
+// the tightest rect around an image's pixels with any opacity, or None if it's fully transparent
+fn opaque_bounds(image: &Image) -> Option<Rect> {
+    let width = image.width as usize;
+    let (mut left, mut top, mut right, mut bottom) = (usize::MAX, usize::MAX, 0, 0);
+    for (i, pixel) in image.get_image_data().iter().enumerate() {
+        if pixel[3] != 0 {
+            let (x, y) = (i % width, i / width);
+            left = left.min(x);
+            top = top.min(y);
+            right = right.max(x + 1);
+            bottom = bottom.max(y + 1);
+        }
+    }
+    if left > right {
+        None
+    } else {
+        Some(Rect::new(
+            left as f32,
+            top as f32,
+            (right - left) as f32,
+            (bottom - top) as f32,
+        ))
+    }
 }
 
-impl Patch {
-    fn width(&self) -> f32 {
-        self.extent.x
-    }
+// loads every png in `dir`; with `trim`, fully transparent borders are cropped off each image.
+// images are read without macroquad's loader, which needs a window, so headless runs can
+// pass `textures: false` to skip uploading them. textures are returned keyed by patch id
+fn load_images(
+    config: PackingConfig,
+    dir: &Path,
+    trim: bool,
+    align: u32,
+    textures: bool,
+) -> (InitialState, HashMap<i32, Texture2D>) {
+    let mut loader = ImageLoader::new(dir, trim, align, textures);
+    while loader.load_next() {}
+    loader.finish(config)
+}
 
-    fn height(&self) -> f32 {
-        self.extent.y
+// like `load_images`, but draws a progress bar between batches of images so a big directory
+// doesn't sit behind a blank window; any images which failed are listed before carrying on
+async fn load_images_showing_progress(
+    config: PackingConfig,
+    dir: &Path,
+    trim: bool,
+    align: u32,
+    background: Color,
+) -> (InitialState, HashMap<i32, Texture2D>) {
+    let mut loader = ImageLoader::new(dir, trim, align, true);
+    loop {
+        let batch_start = Instant::now();
+        let mut more = true;
+        while more && batch_start.elapsed() < LOADING_BATCH {
+            more = loader.load_next();
+        }
+        loader.draw_progress(background, false);
+        next_frame().await;
+        if !more {
+            break;
+        }
     }
-
-    fn left(&self) -> f32 {
-        self.center.x - self.extent.x / 2.
+    if !loader.failures.is_empty() {
+        while get_last_key_pressed().is_none() {
+            loader.draw_progress(background, true);
+            next_frame().await;
+        }
     }
+    loader.finish(config)
+}
 
-    fn right(&self) -> f32 {
-        self.center.x + self.extent.x / 2.
+// `image` on a transparent canvas grown to the next multiple of `align` each way, in its top left
+fn padded_to_alignment(image: Image, align: u32) -> Image {
+    let size = aligned_size(Vec2::new(image.width as f32, image.height as f32), align);
+    if size == Vec2::new(image.width as f32, image.height as f32) {
+        return image;
     }
-
-    fn top(&self) -> f32 {
-        self.center.y - self.extent.y / 2.
+    let mut padded =
+        Image::gen_image_color(size.x as u16, size.y as u16, Color::new(0., 0., 0., 0.));
+    let (width, padded_width) = (image.width(), padded.width());
+    let pixels = padded.get_image_data_mut();
+    for (i, pixel) in image.get_image_data().iter().enumerate() {
+        pixels[(i / width) * padded_width + i % width] = *pixel;
     }
+    padded
+}
 
-    fn bottom(&self) -> f32 {
-        self.center.y + self.extent.y / 2.
+// how long loading runs between redraws of the progress screen
+const LOADING_BATCH: Duration = Duration::from_millis(50);
+
+// loads a directory of images one at a time, so loading can be interleaved with drawing
+struct ImageLoader {
+    paths: Vec<PathBuf>,
+    trim: bool,
+    // the multiple each image's kept size is padded up to
+    align: u32,
+    textures: bool,
+    // index into `paths` of the next image to load
+    next: usize,
+    loaded: Vec<(i32, Rect, Option<Texture2D>, Vec2)>,
+    names: HashMap<i32, String>,
+    #[cfg(feature = "alpha-mask")]
+    masks: HashMap<i32, OccupancyMask>,
+    // each image which couldn't be loaded, and why
+    failures: Vec<String>,
+}
+
+impl ImageLoader {
+    fn new(dir: &Path, trim: bool, align: u32, textures: bool) -> Self {
+        let paths = png_paths(dir).unwrap_or_else(|e| {
+            eprintln!("Unable to read image directory {}: {}", dir.display(), e);
+            Vec::new()
+        });
+        ImageLoader {
+            paths,
+            trim,
+            align,
+            textures,
+            next: 0,
+            loaded: Vec::new(),
+            names: HashMap::new(),
+            #[cfg(feature = "alpha-mask")]
+            masks: HashMap::new(),
+            failures: Vec::new(),
+        }
     }
 
-    fn uprighted(&self) -> Self {
-        if self.width() > self.height() {
-            Self {
-                id: self.id,
-                center: self.center,
-                extent: Vec2::new(self.extent.y, self.extent.x),
-                rotation: std::f32::consts::FRAC_PI_2,
-            }
-        } else {
-            *self
+    // loads the next image, returning whether there are more to go
+    fn load_next(&mut self) -> bool {
+        let path = match self.paths.get(self.next) {
+            Some(path) => path.clone(),
+            None => return false,
+        };
+        self.next += 1;
+        if let Err(e) = self.load(&path) {
+            eprintln!("Unable to load {}: {}", path.display(), e);
+            self.failures.push(format!("{}: {}", path.display(), e));
         }
+        self.next < self.paths.len()
     }
 
-    fn with_left_and_top(&self, left: f32, top: f32) -> Self {
-        Self {
-            id: self.id,
-            center: Vec2::new(left + self.extent.x / 2., top + self.extent.y / 2.),
-            extent: self.extent,
-            rotation: self.rotation,
+    fn load(&mut self, path: &Path) -> Result<(), String> {
+        let bytes = std::fs::read(path).map_err(|e| e.to_string())?;
+        // macroquad panics on images it can't decode rather than returning an error
+        let image = std::panic::catch_unwind(|| Image::from_file_with_format(&bytes, None))
+            .map_err(|_| "not a readable png".to_string())?;
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        // ids come from file names, so they match across runs however the directory is
+        // listed; a colliding name takes the next free id
+        let mut id = Patch::stable_id_from_name(&name);
+        while self.names.contains_key(&id) {
+            id = id.wrapping_add(1) & i32::MAX;
+        }
+        let source_extent = Vec2::new(image.width as f32, image.height as f32);
+        let kept = if self.trim {
+            opaque_bounds(&image).unwrap_or_else(|| {
+                eprintln!(
+                    "{} is fully transparent, keeping a single pixel",
+                    path.display()
+                );
+                Rect::new(0., 0., 1., 1.)
+            })
+        } else {
+            Rect::new(0., 0., source_extent.x, source_extent.y)
+        };
+        // aligned patches grow to the right and down, so the kept pixels are padded out to match
+        let kept_image = padded_to_alignment(image.sub_image(kept), self.align);
+        let texture = if self.textures {
+            Some(Texture2D::from_image(&kept_image))
+        } else {
+            None
+        };
+        #[cfg(feature = "alpha-mask")]
+        {
+            let alpha: Vec<u8> = kept_image.get_image_data().iter().map(|p| p[3]).collect();
+            self.masks.insert(
+                id,
+                OccupancyMask::from_alpha(
+                    kept_image.width(),
+                    kept_image.height(),
+                    &alpha,
+                    MASK_CELL_SIZE,
+                ),
+            );
         }
+        self.loaded.push((id, kept, texture, source_extent));
+        self.names.insert(id, name);
+        Ok(())
     }
 
-    fn overlaps(&self, other: &Patch) -> bool {
-        let (x_overlap, y_overlap) = {
-            (
-                self.left() <= other.left() + other.width()
-                    && self.left() + self.width() >= other.left(),
-                self.top() <= other.top() + other.height()
-                    && self.top() + self.height() >= other.top(),
-            )
+    // a progress bar under the name of the image loaded last, with any failures listed below;
+    // once `done`, a prompt to carry on past the failures
+    fn draw_progress(&self, background: Color, done: bool) {
+        clear_background(background);
+        let width = screen_width() * 0.6;
+        let left = (screen_width() - width) / 2.;
+        let top = screen_height() / 2. - 12.;
+        let fraction = self.next as f32 / self.paths.len().max(1) as f32;
+        draw_rectangle(left, top, width, 24., DARKGRAY);
+        draw_rectangle(left, top, width * fraction, 24., WHITE);
+
+        let label = match self.next.checked_sub(1).and_then(|i| self.paths.get(i)) {
+            Some(path) => format!(
+                "Loading {} ({}/{})",
+                path.file_name().unwrap_or_default().to_string_lossy(),
+                self.next,
+                self.paths.len()
+            ),
+            None => "Loading".to_string(),
         };
+        draw_text(&label, left, top - 12., 20.0, GRAY);
+
+        let mut y = top + 56.;
+        if done {
+            let prompt = format!(
+                "{} images couldn't be loaded, press any key to continue",
+                self.failures.len()
+            );
+            draw_text(&prompt, left, y, 20.0, WHITE);
+            y += 28.;
+        }
+        for failure in &self.failures {
+            if y > screen_height() - 20. {
+                break;
+            }
+            draw_text(failure, left, y, 20.0, RED);
+            y += 24.;
+        }
+    }
 
-        x_overlap && y_overlap
+    fn finish(self, config: PackingConfig) -> (InitialState, HashMap<i32, Texture2D>) {
+        // lay the images out on a roughly square grid, like `new` does for random patches
+        let cols = (self.loaded.len() as f32).sqrt().ceil().max(1.);
+        let rows = (self.loaded.len() as f32 / cols).ceil().max(1.);
+        let cell_width = config.width / cols;
+        let cell_height = config.height / rows;
+        let mut patch_textures = HashMap::new();
+        let patches = self
+            .loaded
+            .into_iter()
+            .enumerate()
+            .map(|(i, (id, kept, texture, source_extent))| {
+                let col = (i as f32) % cols;
+                let row = ((i as f32) / cols).floor();
+                if let Some(texture) = texture {
+                    patch_textures.insert(id, texture);
+                }
+                Patch {
+                    id,
+                    center: Vec2::new(
+                        cell_width * col + cell_width / 2.,
+                        cell_height * row + cell_height / 2.,
+                    ),
+                    extent: kept.size(),
+                    rotation: 0.,
+                    source_extent,
+                    source_rotation: 0.,
+                    source_offset: kept.point(),
+                    group: None,
+                }
+            })
+            .collect();
+
+        (
+            InitialState {
+                patches,
+                names: self.names,
+                #[cfg(feature = "alpha-mask")]
+                masks: self.masks,
+            },
+            patch_textures,
+        )
     }
 }
 
-/////////////////////////////////////////////////////////////////////////////////
-
-trait State {
-    fn name(&self) -> &'static str;
-    fn next(&self) -> Option<Box<dyn State>>;
-    fn patches(&self) -> &Vec<Patch>;
+// every png in `dir`, sorted by name
+fn png_paths(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("png"))
+        })
+        .collect();
+    paths.sort();
+    Ok(paths)
 }
 
-#[derive(Clone, Copy)]
-struct PackingConfig {
-    width: f32,
-    height: f32,
-    padding: f32,
+// how long a watched directory must stay unchanged before repacking, so a batch copy repacks
+// once rather than per file
+#[cfg(feature = "watch")]
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(750);
+
+// notices pngs being added, removed or rewritten in a directory, through the OS's file
+// notifications with changes batched by the debouncer. built without the watch feature, it's
+// never started and never reports a change
+struct DirectoryWatch {
+    #[cfg(feature = "watch")]
+    dir: PathBuf,
+    // notifications stop when the debouncer is dropped, so it's kept for as long as the watch
+    #[cfg(feature = "watch")]
+    _debouncer: notify_debouncer_mini::Debouncer<notify::RecommendedWatcher>,
+    #[cfg(feature = "watch")]
+    events: std::sync::mpsc::Receiver<notify_debouncer_mini::DebounceEventResult>,
 }
 
-struct InitialState {
-    patches: Vec<Patch>,
-    config: PackingConfig,
-}
+impl DirectoryWatch {
+    #[cfg(feature = "watch")]
+    fn new(dir: &Path) -> Result<Self, String> {
+        let (sender, events) = std::sync::mpsc::channel();
+        let mut debouncer = notify_debouncer_mini::new_debouncer(WATCH_DEBOUNCE, sender)
+            .map_err(|e| e.to_string())?;
+        debouncer
+            .watcher()
+            .watch(dir, notify::RecursiveMode::NonRecursive)
+            .map_err(|e| e.to_string())?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            _debouncer: debouncer,
+            events,
+        })
+    }
 
-impl InitialState {
-    fn new(config: PackingConfig, cols: i32, rows: i32) -> InitialState {
-        let mut patches: Vec<Patch> = Vec::new();
-        let cell_width = config.width / (cols as f32);
-        let cell_height = config.height / (rows as f32);
-        let max_width = cell_width * 1.1;
-        let max_height = cell_height * 1.1;
-        let min_width = cell_width * 0.5;
-        let min_height = cell_height * 0.5;
-
-        for row in 0..rows {
-            for col in 0..cols {
-                let across_x = (col as f32) / (cols as f32);
-                let across_y = (row as f32) / (rows as f32);
-                let width = rand::gen_range(min_width, max_width);
-                let height = rand::gen_range(min_height, max_height);
-                let center_x = (config.width * across_x) + (cell_width / 2.);
-                let center_y = (config.height * across_y) + (cell_height / 2.);
-                let patch = Patch {
-                    id: patches.len() as i32,
-                    center: Vec2::new(center_x, center_y),
-                    extent: Vec2::new(width, height),
-                    rotation: 0.,
-                };
-                patches.push(patch);
+    #[cfg(not(feature = "watch"))]
+    fn new(_dir: &Path) -> Result<Self, String> {
+        Err("built without the watch feature".to_string())
+    }
+
+    // whether a settled batch of changes touched any png
+    #[cfg(feature = "watch")]
+    fn touches_pngs(&self, result: notify_debouncer_mini::DebounceEventResult) -> bool {
+        match result {
+            Ok(events) => events.iter().any(|event| {
+                event
+                    .path
+                    .extension()
+                    .is_some_and(|ext| ext.eq_ignore_ascii_case("png"))
+            }),
+            Err(e) => {
+                eprintln!("Error watching {}: {}", self.dir.display(), e);
+                false
             }
         }
+    }
 
-        InitialState { patches, config }
+    // true once pngs in the directory have changed and then stayed unchanged for WATCH_DEBOUNCE;
+    // doesn't wait, so the window can check every frame
+    #[cfg(feature = "watch")]
+    fn settled_change(&mut self) -> bool {
+        let mut changed = false;
+        while let Ok(result) = self.events.try_recv() {
+            changed |= self.touches_pngs(result);
+        }
+        if changed {
+            log_info!("{} settled, repacking", self.dir.display());
+        }
+        changed
     }
-}
 
-impl State for InitialState {
-    fn name(&self) -> &'static str {
-        "Initial"
+    #[cfg(not(feature = "watch"))]
+    fn settled_change(&mut self) -> bool {
+        false
     }
 
-    fn next(&self) -> Option<Box<dyn State>> {
-        Some(Box::new(UprightedState::from(self)))
+    // waits for the next settled change to pngs in the directory, returning false if the watch
+    // has stopped
+    #[cfg(feature = "watch")]
+    fn wait_for_change(&mut self) -> bool {
+        while let Ok(result) = self.events.recv() {
+            if self.touches_pngs(result) {
+                log_info!("{} settled, repacking", self.dir.display());
+                return true;
+            }
+        }
+        false
     }
 
-    fn patches(&self) -> &Vec<Patch> {
-        &self.patches
+    #[cfg(not(feature = "watch"))]
+    fn wait_for_change(&mut self) -> bool {
+        false
     }
 }
 
-#[derive(Clone)]
-struct UprightedState {
-    patches: Vec<Patch>,
-    config: PackingConfig,
+// output files are written next to the executable
+fn output_path(file_name: &str) -> PathBuf {
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(|dir| dir.join(file_name)))
+        .unwrap_or_else(|| PathBuf::from(file_name))
+}
+
+/////////////////////////////////////////////////////////////////////////////////
+
+struct Options {
+    rows: i32,
+    cols: i32,
+    padding: f32,
+    // gap kept from the atlas edges; follows `padding` unless given
+    border: Option<f32>,
+    seed: Option<u64>,
+    // hand generated patches to the pipeline in a seeded random order instead of row by row
+    shuffle: bool,
+    // generate patches of a crafted worst case instead of the random grid
+    stress: Option<StressPattern>,
+    // how many patches to generate; without a stress pattern they're scattered at random
+    // rather than laid out on a grid of --cols by --rows
+    count: Option<usize>,
+    image_dir: Option<PathBuf>,
+    // a CSV or JSON list of sprite names and sizes to pack as plain rects, without the images
+    manifest: Option<PathBuf>,
+    atlas_width: Option<f32>,
+    atlas_height: Option<f32>,
+    // with --max-size, pages are this big and as many are packed as it takes; either way a
+    // power of two layout bigger than this is flagged
+    max_size: Option<u32>,
+    stages: Option<String>,
+    // seconds each transition between steps takes to ease in
+    anim_speed: f32,
+    // the curve those transitions follow
+    easing: Easing,
+    // how far exploding spreads patches, as a multiple of their distance from the atlas center
+    explode_factor: f32,
+    format: ExportFormat,
+    on_oversized: OversizedPolicy,
+    // the packer shown beside the main one in comparison mode
+    compare: Packer,
+    trim: bool,
+    // round each patch's size up to a multiple of this, for block compressed textures; patches
+    // scaled down to fit the atlas afterwards lose their alignment
+    align: u32,
+    // pack loaded images numbered as frames of one animation side by side
+    group_frames: bool,
+    // force every patch to this size and lay them out in a grid, for tilesets
+    uniform: Option<Vec2>,
+    // size the atlas to fit the patches to this width over height rather than to the window
+    target_aspect: Option<f32>,
+    // size the atlas to whichever width packs the patches into the least area
+    min_area: bool,
+    // crop exported pages to their patches rather than the whole atlas
+    trim_atlas: bool,
+    orientation: Orientation,
+    extrude: f32,
+    // atlas pixels between grid lines
+    grid_spacing: f32,
+    background: Color,
+    patch_color: Color,
+    outline_color: Color,
+    // the packer and sort the first layout starts with
+    packer: Packer,
+    sort: SortOrder,
+    // let packing upwards slide patches into a lower column
+    probe_columns: bool,
+    // let packing upwards turn patches when that leaves them higher
+    try_turns: bool,
+    overlap_epsilon: f32,
+    // the rows the deterministic rows packer fills, by count or by height
+    fixed_rows: Option<FixedRows>,
+    // pack to completion and write the atlas without opening a window
+    headless: bool,
+    // how much diagnostic output to write to stderr, raised by each --verbose
+    verbose: u8,
+    // repack and export whenever the image directory changes
+    watch: bool,
+    // resize the atlas to the window and repack whenever the window is resized
+    repack_on_resize: bool,
+    // an exported layout to view in place of packing one
+    import: Option<PathBuf>,
+    // where to write the finished layout when the window is closed or Escape pressed
+    export_on_exit: Option<PathBuf>,
+    // where to write packing statistics alongside each export
+    stats: Option<PathBuf>,
+    // print the effective settings as a --config preset and exit
+    dump_config: bool,
+    // pack loaded images by where they're opaque
+    #[cfg(feature = "alpha-mask")]
+    alpha_mask: bool,
+    // the most, in degrees, each generated patch is randomly tilted by
+    #[cfg(feature = "free-rotation")]
+    max_tilt: f32,
 }
 
-impl From<&InitialState> for UprightedState {
-    fn from(state: &InitialState) -> Self {
+impl Default for Options {
+    fn default() -> Self {
         Self {
-            patches: state.patches.iter().map(|r| r.uprighted()).collect(),
-            config: state.config,
+            rows: 6,
+            cols: 3,
+            padding: 4.,
+            border: None,
+            seed: None,
+            shuffle: false,
+            stress: None,
+            count: None,
+            image_dir: None,
+            manifest: None,
+            atlas_width: None,
+            atlas_height: None,
+            max_size: None,
+            stages: None,
+            anim_speed: 1.,
+            easing: Easing::CubicInOut,
+            explode_factor: 0.5,
+            format: ExportFormat::Json,
+            on_oversized: OversizedPolicy::Scale,
+            compare: Packer::Skyline,
+            trim: true,
+            align: 1,
+            group_frames: false,
+            uniform: None,
+            target_aspect: None,
+            min_area: false,
+            trim_atlas: false,
+            orientation: Orientation::AlwaysPortrait,
+            extrude: 0.,
+            grid_spacing: 64.,
+            background: WHITE,
+            patch_color: PATCH_COLOR,
+            outline_color: DARKGRAY,
+            packer: Packer::PackedUpwards,
+            sort: SortOrder::HeightDesc,
+            probe_columns: false,
+            try_turns: false,
+            overlap_epsilon: DEFAULT_OVERLAP_EPSILON,
+            fixed_rows: None,
+            headless: false,
+            verbose: 0,
+            watch: false,
+            repack_on_resize: false,
+            import: None,
+            export_on_exit: None,
+            stats: None,
+            dump_config: false,
+            #[cfg(feature = "alpha-mask")]
+            alpha_mask: false,
+            #[cfg(feature = "free-rotation")]
+            max_tilt: 0.,
         }
     }
 }
 
-impl State for UprightedState {
-    fn name(&self) -> &'static str {
-        "Uprighted"
+impl Options {
+    fn parse(args: impl Iterator<Item = String>) -> Options {
+        let args: Vec<String> = args.collect();
+        let mut options = Options::default();
+        // a preset only supplies starting values, so flags given alongside it override the file
+        if let Some(index) = args.iter().position(|arg| arg == "--config") {
+            match args.get(index + 1) {
+                Some(path) => {
+                    if let Err(e) = options.load_preset(Path::new(path)) {
+                        eprintln!("Unable to load config {}: {}", path, e);
+                        std::process::exit(1);
+                    }
+                }
+                None => eprintln!("Missing value for --config, using defaults"),
+            }
+        }
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--config" => {
+                    args.next();
+                }
+                "--dump-config" => options.dump_config = true,
+                "--rows" => options.rows = Self::value(&arg, &mut args).unwrap_or(options.rows),
+                "--cols" => options.cols = Self::value(&arg, &mut args).unwrap_or(options.cols),
+                "--padding" => {
+                    options.padding = Self::value(&arg, &mut args).unwrap_or(options.padding)
+                }
+                "--border" => options.border = Self::value(&arg, &mut args).or(options.border),
+                "--seed" => options.seed = Self::value(&arg, &mut args).or(options.seed),
+                "--shuffle" => options.shuffle = true,
+                "--stress" => {
+                    let name: Option<String> = Self::value(&arg, &mut args);
+                    match name.as_deref().map(StressPattern::from_name) {
+                        Some(Some(pattern)) => options.stress = Some(pattern),
+                        Some(None) => eprintln!(
+                            "Unknown stress pattern \"{}\", expected squares, mixed or pot",
+                            name.unwrap_or_default()
+                        ),
+                        None => {}
+                    }
+                }
+                "--count" => options.count = Self::value(&arg, &mut args).or(options.count),
+                "--images" => {
+                    options.image_dir = Self::value(&arg, &mut args).or(options.image_dir)
+                }
+                "--manifest" => {
+                    options.manifest = Self::value(&arg, &mut args).or(options.manifest)
+                }
+                "--atlas-width" => {
+                    options.atlas_width = Self::value(&arg, &mut args).or(options.atlas_width)
+                }
+                "--atlas-height" => {
+                    options.atlas_height = Self::value(&arg, &mut args).or(options.atlas_height)
+                }
+                "--max-size" => {
+                    options.max_size = Self::value(&arg, &mut args).or(options.max_size)
+                }
+                "--stages" => options.stages = Self::value(&arg, &mut args).or(options.stages),
+                "--anim-speed" => {
+                    options.anim_speed = Self::value(&arg, &mut args).unwrap_or(options.anim_speed)
+                }
+                "--explode-factor" => {
+                    options.explode_factor =
+                        Self::value(&arg, &mut args).unwrap_or(options.explode_factor)
+                }
+                "--easing" => {
+                    let name: Option<String> = Self::value(&arg, &mut args);
+                    match name.as_deref().map(Easing::from_name) {
+                        Some(Some(easing)) => options.easing = easing,
+                        Some(None) => eprintln!(
+                            "Unknown easing \"{}\", using default",
+                            name.unwrap_or_default()
+                        ),
+                        None => {}
+                    }
+                }
+                "--format" => {
+                    let name: Option<String> = Self::value(&arg, &mut args);
+                    match name.as_deref().map(ExportFormat::from_name) {
+                        Some(Some(format)) => options.format = format,
+                        Some(None) => eprintln!(
+                            "Unknown export format \"{}\", using default",
+                            name.unwrap_or_default()
+                        ),
+                        None => {}
+                    }
+                }
+                "--no-trim" => options.trim = false,
+                "--align" => {
+                    options.align = Self::value(&arg, &mut args).unwrap_or(options.align).max(1)
+                }
+                "--trim-atlas" => options.trim_atlas = true,
+                "--group-frames" => options.group_frames = true,
+                "--min-area" => options.min_area = true,
+                "--target-aspect" => {
+                    let aspect: Option<String> = Self::value(&arg, &mut args);
+                    match aspect.as_deref().map(parse_aspect) {
+                        Some(Some(aspect)) => options.target_aspect = Some(aspect),
+                        Some(None) => eprintln!(
+                            "Invalid aspect \"{}\" for --target-aspect, expected W:H",
+                            aspect.unwrap_or_default()
+                        ),
+                        None => {}
+                    }
+                }
+                "--uniform" => {
+                    let size: Option<String> = Self::value(&arg, &mut args);
+                    match size.as_deref().map(parse_tile_size) {
+                        Some(Some(tile)) => options.uniform = Some(tile),
+                        Some(None) => eprintln!(
+                            "Invalid tile size \"{}\" for --uniform, expected WxH",
+                            size.unwrap_or_default()
+                        ),
+                        None => {}
+                    }
+                }
+                // for engines which can't draw rotated frames
+                "--no-rotation" => options.orientation = Orientation::Never,
+                "--headless" => options.headless = true,
+                "--verbose" => options.verbose = options.verbose.saturating_add(1),
+                "--watch" => options.watch = true,
+                "--repack-on-resize" => options.repack_on_resize = true,
+                "--import" => options.import = Self::value(&arg, &mut args).or(options.import),
+                "--stats" => options.stats = Self::value(&arg, &mut args).or(options.stats),
+                "--export-on-exit" => {
+                    options.export_on_exit = Self::value(&arg, &mut args).or(options.export_on_exit)
+                }
+                "--probe-columns" => options.probe_columns = true,
+                "--try-turns" => options.try_turns = true,
+                "--row-count" => {
+                    let count: Option<usize> = Self::value(&arg, &mut args);
+                    match count.filter(|&count| count > 0) {
+                        Some(count) => options.fixed_rows = Some(FixedRows::Count(count)),
+                        None => eprintln!("--row-count needs at least one row"),
+                    }
+                }
+                "--row-height" => {
+                    let height: Option<f32> = Self::value(&arg, &mut args);
+                    match height.filter(|&height| height > 0.) {
+                        Some(height) => options.fixed_rows = Some(FixedRows::Height(height)),
+                        None => eprintln!("--row-height needs a positive height"),
+                    }
+                }
+                "--overlap-epsilon" => {
+                    options.overlap_epsilon = Self::value(&arg, &mut args)
+                        .unwrap_or(options.overlap_epsilon)
+                        .max(0.)
+                }
+                "--bg" => {
+                    options.background = Self::color(&arg, &mut args).unwrap_or(options.background)
+                }
+                "--patch-color" => {
+                    options.patch_color =
+                        Self::color(&arg, &mut args).unwrap_or(options.patch_color)
+                }
+                #[cfg(feature = "alpha-mask")]
+                "--alpha-mask" => options.alpha_mask = true,
+                #[cfg(feature = "free-rotation")]
+                "--tilt" => {
+                    options.max_tilt = Self::value(&arg, &mut args).unwrap_or(options.max_tilt)
+                }
+                "--outline-color" => {
+                    options.outline_color =
+                        Self::color(&arg, &mut args).unwrap_or(options.outline_color)
+                }
+                "--grid" => {
+                    options.grid_spacing =
+                        Self::value(&arg, &mut args).unwrap_or(options.grid_spacing)
+                }
+                "--extrude" => {
+                    options.extrude = Self::value(&arg, &mut args).unwrap_or(options.extrude)
+                }
+                "--orientation" => {
+                    let name: Option<String> = Self::value(&arg, &mut args);
+                    match name.as_deref().map(Orientation::from_name) {
+                        Some(Some(orientation)) => options.orientation = orientation,
+                        Some(None) => eprintln!(
+                            "Unknown orientation \"{}\", using default",
+                            name.unwrap_or_default()
+                        ),
+                        None => {}
+                    }
+                }
+                "--compare" => {
+                    let name: Option<String> = Self::value(&arg, &mut args);
+                    match name.as_deref().map(Packer::from_name) {
+                        Some(Some(packer)) => options.compare = packer,
+                        Some(None) => eprintln!(
+                            "Unknown packer \"{}\", using default",
+                            name.unwrap_or_default()
+                        ),
+                        None => {}
+                    }
+                }
+                "--on-oversized" => {
+                    let name: Option<String> = Self::value(&arg, &mut args);
+                    match name.as_deref().map(OversizedPolicy::from_name) {
+                        Some(Some(policy)) => options.on_oversized = policy,
+                        Some(None) => eprintln!(
+                            "Unknown oversized policy \"{}\", using default",
+                            name.unwrap_or_default()
+                        ),
+                        None => {}
+                    }
+                }
+                _ => eprintln!("Ignoring unrecognized argument \"{}\"", arg),
+            }
+        }
+
+        if options.padding < 0. {
+            eprintln!("Padding can't be negative, using 0");
+            options.padding = 0.;
+        }
+        if options.min_area && options.target_aspect.is_some() {
+            eprintln!("--min-area and --target-aspect both size the atlas, using --target-aspect");
+            options.min_area = false;
+        }
+        if options.border.is_some_and(|border| border < 0.) {
+            eprintln!("Border can't be negative, using 0");
+            options.border = Some(0.);
+        }
+        if options.grid_spacing < 1. {
+            eprintln!("Grid spacing must be at least 1, using 64");
+            options.grid_spacing = 64.;
+        }
+        if options.extrude < 0. {
+            eprintln!("Extrusion can't be negative, using 0");
+            options.extrude = 0.;
+        }
+        options
+    }
+
+    // applies the settings in a JSON preset, failing on the first malformed or unknown one
+    fn load_preset(&mut self, path: &Path) -> Result<(), String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let settings = match json::parse(&text)? {
+            json::Value::Object(settings) => settings,
+            _ => return Err("expected an object of settings".to_string()),
+        };
+        for (key, value) in &settings {
+            let number = || {
+                value
+                    .as_f32()
+                    .ok_or_else(|| format!("\"{}\" should be a number", key))
+            };
+            let whole_number = || {
+                value
+                    .as_i64()
+                    .map(|n| n as i32)
+                    .ok_or_else(|| format!("\"{}\" should be a whole number", key))
+            };
+            let string = || {
+                value
+                    .as_str()
+                    .ok_or_else(|| format!("\"{}\" should be a string", key))
+            };
+            let unknown = |name: &str| format!("unknown {} \"{}\"", key, name);
+            let color = || {
+                string().and_then(|hex| {
+                    parse_hex_color(hex).ok_or_else(|| {
+                        format!("\"{}\" should be a #RRGGBB or #RRGGBBAA color", key)
+                    })
+                })
+            };
+            match key.as_str() {
+                "rows" => self.rows = whole_number()?,
+                "cols" => self.cols = whole_number()?,
+                "padding" => self.padding = number()?,
+                "border" => self.border = Some(number()?),
+                "atlas_width" => self.atlas_width = Some(number()?),
+                "atlas_height" => self.atlas_height = Some(number()?),
+                "extrude" => self.extrude = number()?,
+                "orientation" => {
+                    let name = string()?;
+                    self.orientation = Orientation::from_name(name).ok_or_else(|| unknown(name))?
+                }
+                "packer" => {
+                    let name = string()?;
+                    self.packer = Packer::from_name(name).ok_or_else(|| unknown(name))?
+                }
+                "sort" => {
+                    let name = string()?;
+                    self.sort = SortOrder::from_name(name).ok_or_else(|| unknown(name))?
+                }
+                "background" => self.background = color()?,
+                "patch_color" => self.patch_color = color()?,
+                "outline_color" => self.outline_color = color()?,
+                _ => return Err(format!("unknown setting \"{}\"", key)),
+            }
+        }
+        Ok(())
+    }
+
+    // the settings a preset can hold, in the same form `load_preset` reads
+    fn preset_json(&self) -> String {
+        let mut settings = vec![
+            format!("\"rows\": {}", self.rows),
+            format!("\"cols\": {}", self.cols),
+            format!("\"padding\": {}", self.padding),
+        ];
+        if let Some(border) = self.border {
+            settings.push(format!("\"border\": {}", border));
+        }
+        if let Some(width) = self.atlas_width {
+            settings.push(format!("\"atlas_width\": {}", width));
+        }
+        if let Some(height) = self.atlas_height {
+            settings.push(format!("\"atlas_height\": {}", height));
+        }
+        settings.extend(vec![
+            format!("\"extrude\": {}", self.extrude),
+            format!("\"orientation\": {}", json::escape(self.orientation.name())),
+            format!("\"packer\": {}", json::escape(self.packer.name())),
+            format!("\"sort\": {}", json::escape(self.sort.name())),
+            format!("\"background\": \"{}\"", hex_color(self.background)),
+            format!("\"patch_color\": \"{}\"", hex_color(self.patch_color)),
+            format!("\"outline_color\": \"{}\"", hex_color(self.outline_color)),
+        ]);
+        let lines: Vec<String> = settings.iter().map(|s| format!("    {}", s)).collect();
+        format!("{{\n{}\n}}\n", lines.join(",\n"))
     }
 
-    fn next(&self) -> Option<Box<dyn State>> {
-        Some(Box::new(SortedByHeightState::from(self)))
+    fn color(arg: &str, args: &mut impl Iterator<Item = String>) -> Option<Color> {
+        let hex: String = Self::value(arg, args)?;
+        let color = parse_hex_color(&hex);
+        if color.is_none() {
+            eprintln!(
+                "Invalid color \"{}\" for {}, expected #RRGGBB or #RRGGBBAA",
+                hex, arg
+            );
+        }
+        color
     }
 
-    fn patches(&self) -> &Vec<Patch> {
-        &self.patches
+    // consumes the value following `arg`, warning and returning None if it's missing or
+    // doesn't parse so the caller can keep its default
+    fn value<T: std::str::FromStr>(
+        arg: &str,
+        args: &mut impl Iterator<Item = String>,
+    ) -> Option<T> {
+        let value = match args.next() {
+            Some(value) => value,
+            None => {
+                eprintln!("Missing value for {}, using default", arg);
+                return None;
+            }
+        };
+        let parsed = value.parse().ok();
+        if parsed.is_none() {
+            eprintln!("Invalid value \"{}\" for {}, using default", value, arg);
+        }
+        parsed
     }
 }
 
-#[derive(Clone)]
-struct SortedByHeightState {
-    patches: Vec<Patch>,
-    config: PackingConfig,
+fn conf() -> Conf {
+    Conf {
+        window_title: String::from("Texture Packer"),
+        window_width: 768,
+        window_height: 768,
+        fullscreen: false,
+        ..Default::default()
+    }
 }
 
-impl From<&UprightedState> for SortedByHeightState {
-    fn from(state: &UprightedState) -> Self {
-        let mut sorted_by_height = state.patches.clone();
-        sorted_by_height.sort_by(|a, b| b.height().partial_cmp(&a.height()).unwrap());
+// maps atlas coordinates onto the window; at zoom 1 with no pan the whole atlas is scaled to fit
+// while keeping its aspect, and zoom/pan are applied in screen space on top of that
+struct View {
+    zoom: f32,
+    pan: Vec2,
+}
 
-        let mut arranged_by_height: Vec<Patch> = Vec::new();
-        for patch in sorted_by_height {
-            arranged_by_height.push(if let Some(last) = arranged_by_height.last() {
-                patch.with_left_and_top(last.right() + state.config.padding, state.config.padding)
-            } else {
-                patch.with_left_and_top(state.config.padding, state.config.padding)
-            });
-        }
+impl View {
+    const MIN_ZOOM: f32 = 0.1;
+    const MAX_ZOOM: f32 = 20.;
+
+    fn new() -> Self {
         Self {
-            patches: arranged_by_height,
-            config: state.config,
+            zoom: 1.,
+            pan: Vec2::ZERO,
         }
     }
-}
 
-impl State for SortedByHeightState {
-    fn name(&self) -> &'static str {
-        "Sorted by Height"
+    fn scale(&self, config: &PackingConfig) -> f32 {
+        Self::fit(config, Vec2::new(screen_width(), screen_height())) * self.zoom
+    }
+
+    // the scale at which the whole atlas fits a window of `screen` size
+    fn fit(config: &PackingConfig, screen: Vec2) -> f32 {
+        (screen.x / config.width).min(screen.y / config.height)
+    }
+
+    // keeps the same part of the atlas in view after the window went from `from` to `to` in
+    // size; the atlas is rescaled to fit the new window, so the pan scales with it
+    fn resized(&mut self, config: &PackingConfig, from: Vec2, to: Vec2) {
+        self.pan *= Self::fit(config, to) / Self::fit(config, from);
+    }
+
+    fn screen_to_world(&self, config: &PackingConfig, point: Vec2) -> Vec2 {
+        (point - self.pan) / self.scale(config)
     }
 
-    fn next(&self) -> Option<Box<dyn State>> {
-        Some(Box::new(FlowedState::from(self)))
+    // zooms by `factor` while keeping the atlas point under `cursor` fixed on screen
+    fn zoom_about(&mut self, config: &PackingConfig, cursor: Vec2, factor: f32) {
+        let world = self.screen_to_world(config, cursor);
+        self.zoom = (self.zoom * factor).clamp(Self::MIN_ZOOM, Self::MAX_ZOOM);
+        self.pan = cursor - world * self.scale(config);
     }
 
-    fn patches(&self) -> &Vec<Patch> {
-        &self.patches
+    fn camera(&self, config: &PackingConfig) -> Camera2D {
+        let scale = self.scale(config);
+        Camera2D::from_display_rect(Rect::new(
+            -self.pan.x / scale,
+            -self.pan.y / scale,
+            screen_width() / scale,
+            screen_height() / scale,
+        ))
     }
 }
 
-#[derive(Clone)]
-struct FlowedState {
-    patches: Vec<Patch>,
-    config: PackingConfig,
+// the curve transitions follow from their old layout to their new one
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Easing {
+    Linear,
+    CubicInOut,
+    QuadOut,
+    // pulls back a little before setting off and overshoots a little before settling
+    BackInOut,
 }
 
-impl From<&SortedByHeightState> for FlowedState {
-    fn from(state: &SortedByHeightState) -> Self {
-        let padding = state.config.padding;
-        let mut current_y = padding;
-        let mut current_x = padding;
-        let mut row_height = 0f32;
-        let mut result: Vec<Patch> = Vec::new();
-        let mut row = 0;
+impl Easing {
+    fn cycled(self) -> Easing {
+        match self {
+            Easing::Linear => Easing::CubicInOut,
+            Easing::CubicInOut => Easing::QuadOut,
+            Easing::QuadOut => Easing::BackInOut,
+            Easing::BackInOut => Easing::Linear,
+        }
+    }
 
-        for patch in &state.patches {
-            if row % 2 == 0 {
-                if current_x + patch.width() > state.config.width {
-                    current_x = state.config.width - padding - patch.width();
-                    current_y += row_height;
-                    row_height = 0f32;
-                    row += 1;
-                }
-            } else {
-                current_x -= patch.width() + padding;
-                if current_x < padding {
-                    current_x = padding;
-                    current_y += row_height;
-                    row_height = 0.;
-                    row += 1;
-                }
-            }
+    fn name(self) -> &'static str {
+        match self {
+            Easing::Linear => "linear",
+            Easing::CubicInOut => "cubic",
+            Easing::QuadOut => "quad-out",
+            Easing::BackInOut => "back",
+        }
+    }
 
-            result.push(patch.with_left_and_top(current_x, current_y));
-            row_height = row_height.max(patch.height() + padding);
+    fn from_name(name: &str) -> Option<Easing> {
+        match name {
+            "linear" => Some(Easing::Linear),
+            "cubic" => Some(Easing::CubicInOut),
+            "quad-out" => Some(Easing::QuadOut),
+            "back" => Some(Easing::BackInOut),
+            _ => None,
+        }
+    }
 
-            if row % 2 == 0 {
-                current_x += patch.width() + padding;
+    // how far along the curve is `t` of the way through, both running from 0 to 1
+    fn apply(self, t: f32) -> f32 {
+        match self {
+            Easing::Linear => t,
+            Easing::CubicInOut => {
+                if t < 0.5 {
+                    4. * t * t * t
+                } else {
+                    1. - (2. - 2. * t).powi(3) / 2.
+                }
+            }
+            Easing::QuadOut => 1. - (1. - t) * (1. - t),
+            Easing::BackInOut => {
+                const OVERSHOOT: f32 = 1.70158 * 1.525;
+                let t = 2. * t;
+                if t < 1. {
+                    t * t * ((OVERSHOOT + 1.) * t - OVERSHOOT) / 2.
+                } else {
+                    let t = t - 2.;
+                    (t * t * ((OVERSHOOT + 1.) * t + OVERSHOOT) + 2.) / 2.
+                }
             }
         }
+    }
+}
 
-        Self {
-            patches: result,
-            config: state.config,
-        }
+// eases from 0 to 1 over `duration` seconds; a zero or negative duration snaps straight to 1
+fn ease_unit(t: f32, duration: f32, easing: Easing) -> f32 {
+    if duration <= 0. {
+        1.
+    } else {
+        easing.apply(t.clamp(0., duration) / duration)
     }
 }
 
-impl State for FlowedState {
-    fn name(&self) -> &'static str {
-        "Flowed"
+// lines of text in a dark box beside the cursor
+fn draw_tooltip(lines: &[String], cursor: Vec2) {
+    let width = lines
+        .iter()
+        .map(|line| measure_text(line, None, 20, 1.0).width)
+        .fold(0., f32::max);
+    let origin = cursor + Vec2::new(16., 16.);
+    draw_rectangle(
+        origin.x,
+        origin.y,
+        width + 16.,
+        lines.len() as f32 * 20. + 12.,
+        Color::new(0., 0., 0., 0.75),
+    );
+    for (i, line) in lines.iter().enumerate() {
+        draw_text(
+            line,
+            origin.x + 8.,
+            origin.y + 22. + 20. * i as f32,
+            20.0,
+            WHITE,
+        );
     }
+}
 
-    fn next(&self) -> Option<Box<dyn State>> {
-        Some(Box::new(PackedUpwardsState::from(self)))
+// everything the viewer can be told to do, from its key or from the control panel
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Command {
+    Step,
+    Back,
+    AutoAdvance,
+    Regenerate,
+    CyclePacker,
+    Repack,
+    CycleSort,
+    Compare,
+    Export,
+    ExportImages,
+    SaveSession,
+    LoadSession,
+    PrintTable,
+    PreviousPage,
+    NextPage,
+    ResetView,
+    Padding,
+    Grid,
+    Outlines,
+    IdColors,
+    Labels,
+    FreeRects,
+    Explode,
+    Easing,
+    Panel,
+    Quit,
+}
+
+impl Command {
+    // the commands given buttons on the control panel, top to bottom
+    const PANEL: [Command; 9] = [
+        Command::Step,
+        Command::Back,
+        Command::AutoAdvance,
+        Command::Regenerate,
+        Command::CyclePacker,
+        Command::Repack,
+        Command::CycleSort,
+        Command::Export,
+        Command::Labels,
+    ];
+
+    fn key(self) -> KeyCode {
+        match self {
+            Command::Step => KeyCode::Space,
+            Command::Back => KeyCode::Left,
+            Command::AutoAdvance => KeyCode::P,
+            Command::Regenerate => KeyCode::R,
+            Command::CyclePacker => KeyCode::M,
+            Command::Repack => KeyCode::A,
+            Command::CycleSort => KeyCode::O,
+            Command::Compare => KeyCode::V,
+            Command::Export => KeyCode::S,
+            Command::ExportImages => KeyCode::C,
+            Command::SaveSession => KeyCode::F5,
+            Command::LoadSession => KeyCode::F9,
+            Command::PrintTable => KeyCode::T,
+            Command::PreviousPage => KeyCode::LeftBracket,
+            Command::NextPage => KeyCode::RightBracket,
+            Command::ResetView => KeyCode::Key0,
+            Command::Padding => KeyCode::D,
+            Command::Grid => KeyCode::G,
+            Command::Outlines => KeyCode::B,
+            Command::IdColors => KeyCode::H,
+            Command::Labels => KeyCode::L,
+            Command::FreeRects => KeyCode::F,
+            Command::Explode => KeyCode::X,
+            Command::Easing => KeyCode::E,
+            Command::Panel => KeyCode::Tab,
+            Command::Quit => KeyCode::Escape,
+        }
     }
 
-    fn patches(&self) -> &Vec<Patch> {
-        &self.patches
+    fn key_name(self) -> &'static str {
+        match self.key() {
+            KeyCode::Space => "Space",
+            KeyCode::Left => "Left",
+            KeyCode::Tab => "Tab",
+            KeyCode::Escape => "Esc",
+            KeyCode::F5 => "F5",
+            KeyCode::F9 => "F9",
+            KeyCode::LeftBracket => "[",
+            KeyCode::RightBracket => "]",
+            KeyCode::Key0 => "0",
+            KeyCode::P => "P",
+            KeyCode::R => "R",
+            KeyCode::M => "M",
+            KeyCode::A => "A",
+            KeyCode::O => "O",
+            KeyCode::V => "V",
+            KeyCode::S => "S",
+            KeyCode::C => "C",
+            KeyCode::T => "T",
+            KeyCode::D => "D",
+            KeyCode::G => "G",
+            KeyCode::B => "B",
+            KeyCode::H => "H",
+            KeyCode::L => "L",
+            KeyCode::F => "F",
+            KeyCode::X => "X",
+            KeyCode::E => "E",
+            _ => "?",
+        }
     }
-}
 
-#[derive(Clone)]
-struct PackedUpwardsState {
-    patches: Vec<Patch>,
-    config: PackingConfig,
+    fn label(self) -> &'static str {
+        match self {
+            Command::Step => "step",
+            Command::Back => "back",
+            Command::AutoAdvance => "auto-advance",
+            Command::Regenerate => "regenerate",
+            Command::CyclePacker => "next packer",
+            Command::Repack => "repack with next",
+            Command::CycleSort => "next sort",
+            Command::Compare => "compare",
+            Command::Export => "export",
+            Command::ExportImages => "export images",
+            Command::SaveSession => "save session",
+            Command::LoadSession => "load session",
+            Command::PrintTable => "print table",
+            Command::PreviousPage => "previous page",
+            Command::NextPage => "next page",
+            Command::ResetView => "reset view",
+            Command::Padding => "padding",
+            Command::Grid => "grid",
+            Command::Outlines => "outlines",
+            Command::IdColors => "color by id",
+            Command::Labels => "labels",
+            Command::FreeRects => "free rects",
+            Command::Explode => "explode",
+            Command::Easing => "next easing",
+            Command::Panel => "control panel",
+            Command::Quit => "quit",
+        }
+    }
 }
 
-impl From<&FlowedState> for PackedUpwardsState {
-    fn from(state: &FlowedState) -> Self {
-        let mut result = Vec::new();
+// clickable buttons down the left of the window for the commonest commands, each naming its key
+struct ControlPanel {
+    visible: bool,
+}
 
-        for patch in &state.patches {
-            // define a rect going from top of this rect to top of screen
-            let test_height = patch.top() - 1.;
-            let test = Patch {
-                id: -1,
-                center: Vec2::new(patch.center.x, test_height / 2.),
-                extent: Vec2::new(patch.width(), test_height),
-                rotation: 0.,
-            };
+impl ControlPanel {
+    const LEFT: f32 = 20.;
+    const TOP: f32 = 150.;
+    const BUTTON_WIDTH: f32 = 200.;
+    const BUTTON_HEIGHT: f32 = 26.;
+    const SPACING: f32 = 4.;
+
+    fn buttons(&self) -> impl Iterator<Item = (Command, Rect)> {
+        Command::PANEL.iter().enumerate().map(|(i, &command)| {
+            let top = Self::TOP + 30. + i as f32 * (Self::BUTTON_HEIGHT + Self::SPACING);
+            let rect = Rect::new(Self::LEFT, top, Self::BUTTON_WIDTH, Self::BUTTON_HEIGHT);
+            (command, rect)
+        })
+    }
 
-            let mut bottom: f32 = 0.;
-            for candidate in Self::find_intersections(test, &result) {
-                bottom = bottom.max(candidate.bottom());
-            }
-            result.push(patch.with_left_and_top(patch.left(), bottom + state.config.padding));
+    // the command whose button was clicked this frame, if the panel is showing
+    fn clicked(&self, mouse: Vec2) -> Option<Command> {
+        if !self.visible || !is_mouse_button_pressed(MouseButton::Left) {
+            return None;
         }
+        self.buttons()
+            .find(|(_, rect)| rect.contains(mouse))
+            .map(|(command, _)| command)
+    }
 
-        Self {
-            patches: result,
-            config: state.config,
+    // the buttons beneath a line of what's showing, in screen space
+    fn draw(&self, title: &str, mouse: Vec2) {
+        if !self.visible {
+            return;
+        }
+        draw_text(title, Self::LEFT, Self::TOP + 18., 20.0, DARKGRAY);
+        for (command, rect) in self.buttons() {
+            let background = if rect.contains(mouse) {
+                Color::new(0., 0., 0., 0.85)
+            } else {
+                Color::new(0., 0., 0., 0.6)
+            };
+            draw_rectangle(rect.x, rect.y, rect.w, rect.h, background);
+            draw_text(command.label(), rect.x + 8., rect.y + 18., 20.0, WHITE);
+            let key = command.key_name();
+            let key_width = measure_text(key, None, 20, 1.0).width;
+            draw_text(
+                key,
+                rect.right() - key_width - 8.,
+                rect.y + 18.,
+                20.0,
+                LIGHTGRAY,
+            );
         }
     }
 }
 
-impl PackedUpwardsState {
-    fn find_intersections(test: Patch, among: &[Patch]) -> Vec<Patch> {
-        among.iter().filter(|p| test.overlaps(p)).copied().collect()
-    }
+// spreads patches out from the atlas center so close neighbors visibly separate, easing out when
+// toggled on and back in when toggled off
+struct Explode {
+    on: bool,
+    toggled_at: Option<f64>,
 }
 
-impl State for PackedUpwardsState {
-    fn name(&self) -> &'static str {
-        "Packed Upwards"
+impl Explode {
+    fn new() -> Self {
+        Self {
+            on: false,
+            toggled_at: None,
+        }
     }
 
-    fn next(&self) -> Option<Box<dyn State>> {
-        None
+    fn toggle(&mut self, now: f64) {
+        self.on = !self.on;
+        self.toggled_at = Some(now);
     }
 
-    fn patches(&self) -> &Vec<Patch> {
-        &self.patches
+    // how far spread out patches are, from 0 when packed to 1 when fully exploded
+    fn amount(&self, now: f64, duration: f32) -> f32 {
+        let t = self.toggled_at.map_or(1., |at| {
+            ease_unit((now - at) as f32, duration, Easing::CubicInOut)
+        });
+        if self.on {
+            t
+        } else {
+            1. - t
+        }
     }
 }
 
-/////////////////////////////////////////////////////////////////////////////////
+// `patches` pushed away from the atlas center by `spread` times their distance from it
+fn exploded(patches: &[Patch], config: &PackingConfig, spread: f32) -> Vec<Patch> {
+    let middle = Vec2::new(config.width / 2., config.height / 2.);
+    patches
+        .iter()
+        .map(|p| Patch {
+            center: p.center + (p.center - middle) * spread,
+            ..*p
+        })
+        .collect()
+}
 
-fn conf() -> Conf {
-    Conf {
-        window_title: String::from("Texture Packer"),
-        window_width: 768,
-        window_height: 768,
-        fullscreen: false,
-        ..Default::default()
+fn lerp_angle(a: f32, b: f32, t: f32) -> f32 {
+    let tau = std::f32::consts::PI * 2.;
+    let mut delta = (b - a) % tau;
+    if delta > std::f32::consts::PI {
+        delta -= tau;
+    } else if delta < -std::f32::consts::PI {
+        delta += tau;
     }
+    a + delta * t
 }
 
-fn ease(t: f32, b: f32, c: f32, d: f32) -> f32 {
-    let t = t / (d / 2.);
-    if t < 1. {
-        c / 2. * t * t * t + b
-    } else {
-        let t = t - 2.;
-        c / 2. * (t * t * t + 2.) + b
+fn draw_rotated_rectangle(center: Vec2, size: Vec2, rotation: f32, color: Color) {
+    let [top_left, top_right, bottom_right, bottom_left] = rotated_corners(center, size, rotation);
+    draw_triangle(top_left, top_right, bottom_right, color);
+    draw_triangle(top_left, bottom_right, bottom_left, color);
+}
+
+fn draw_rotated_rectangle_lines(
+    center: Vec2,
+    size: Vec2,
+    rotation: f32,
+    thickness: f32,
+    color: Color,
+) {
+    let corners = rotated_corners(center, size, rotation);
+    for i in 0..corners.len() {
+        let (a, b) = (corners[i], corners[(i + 1) % corners.len()]);
+        draw_line(a.x, a.y, b.x, b.y, thickness, color);
     }
 }
 
-fn ease_unit(t: f32) -> f32 {
-    ease(t.clamp(0., 1.), 0., 1., 1.)
+fn draw_patch_body(
+    center: Vec2,
+    size: Vec2,
+    rotation: f32,
+    texture: Option<Texture2D>,
+    color: Color,
+    outline: Option<Color>,
+) {
+    if let Some(texture) = texture {
+        draw_texture_ex(
+            texture,
+            center.x - size.x / 2.,
+            center.y - size.y / 2.,
+            WHITE,
+            DrawTextureParams {
+                dest_size: Some(size),
+                rotation,
+                ..Default::default()
+            },
+        );
+    } else {
+        draw_rotated_rectangle(center, size, rotation, color);
+    }
+    if let Some(outline) = outline {
+        draw_rotated_rectangle_lines(center, size, rotation, 1., outline);
+    }
 }
 
-fn draw_patches(patches: &[Patch], color: Color) {
+// outlines the padding around each patch; each patch owns half the gap on every side, so the
+// outlines of correctly spaced neighbors just touch
+fn draw_padding(patches: &[Patch], padding: f32, color: Color) {
     for patch in patches {
-        draw_rectangle(
-            patch.left(),
-            patch.top(),
-            patch.width(),
-            patch.height(),
+        draw_rectangle_lines(
+            patch.left() - padding / 2.,
+            patch.top() - padding / 2.,
+            patch.width() + padding,
+            patch.height() + padding,
+            1.,
             color,
         );
-        draw_text(
-            format!("{}", patch.id).as_str(),
-            patch.center.x,
-            patch.center.y,
-            16.,
-            WHITE,
-        );
     }
 }
 
-fn draw_interpolated_patches(old_patches: &[Patch], new_patches: &[Patch], t: f32, color: Color) {
-    let t = t.clamp(0., 1.);
-    let t = ease_unit(t);
-    for (old, current) in old_patches.iter().zip(new_patches.iter()) {
-        let center = old.center + t * (current.center - old.center);
-        let extent = old.extent + t * (current.extent - old.extent);
+// shades the free space a packer was tracking, outlining each rect since they may overlap
+fn draw_free_rects(free_rects: &[Patch]) {
+    for rect in free_rects {
         draw_rectangle(
-            center.x - extent.x / 2.,
-            center.y - extent.y / 2.,
-            extent.x,
-            extent.y,
-            color,
+            rect.left(),
+            rect.top(),
+            rect.width(),
+            rect.height(),
+            Color::new(0.2, 0.8, 0.3, 0.12),
         );
-        draw_text(
-            format!("{}", current.id).as_str(),
-            center.x,
-            center.y,
-            16.,
-            WHITE,
+        draw_rectangle_lines(
+            rect.left(),
+            rect.top(),
+            rect.width(),
+            rect.height(),
+            1.,
+            Color::new(0.1, 0.6, 0.2, 0.6),
         );
     }
 }
 
-#[macroquad::main(conf)]
-async fn main() {
-    let rows = 6;
-    let cols = 3;
-    let config = PackingConfig {
-        width: screen_width(),
-        height: screen_height(),
-        padding: 4.,
-    };
-    let mut previous_state: Option<Box<dyn State>> = None;
-    let mut state: Box<dyn State> = Box::new(InitialState::new(config, cols, rows));
-    let mut last_step_time = None;
-    let patch_color: Color = [60, 60, 60, 128].into();
+// a line every `spacing` atlas pixels, labeled along the top and left edges, inside the atlas
+// boundary; drawn in atlas space so it pans and zooms with the patches
+fn draw_grid(config: &PackingConfig, spacing: f32) {
+    let color = Color::new(0., 0., 0., 0.15);
+    let mut x = 0.;
+    while x <= config.width {
+        draw_line(x, 0., x, config.height, 1., color);
+        draw_text(&format!("{}", x), x + 2., -4., 16., GRAY);
+        x += spacing;
+    }
+    let mut y = spacing;
+    while y <= config.height {
+        draw_line(0., y, config.width, y, 1., color);
+        let label = format!("{}", y);
+        let label_width = measure_text(&label, None, 16, 1.0).width;
+        draw_text(&label, -label_width - 4., y + 4., 16., GRAY);
+        y += spacing;
+    }
+    draw_rectangle_lines(0., 0., config.width, config.height, 2., DARKGRAY);
+}
 
-    loop {
-        if is_key_pressed(KeyCode::Space) {
-            if let Some(new_state) = state.next() {
-                previous_state = Some(state);
-                state = new_state;
-                last_step_time = Some(get_time());
+// the area consumed by padding, using the same half-gap-per-side split as `draw_padding`
+fn padding_area(patches: &[Patch], padding: f32) -> f32 {
+    patches
+        .iter()
+        .map(|p| (p.width() + padding) * (p.height() + padding) - p.width() * p.height())
+        .sum()
+}
+
+struct PatchStyle {
+    color: Color,
+    rotated_color: Color,
+    // a 1px border around each patch, so neighbors packed flush can be told apart
+    outline: Option<Color>,
+    // loaded images keyed by patch id; patches without one are drawn as flat rects
+    textures: HashMap<i32, Texture2D>,
+    // draw each patch's id over it
+    labels: bool,
+    // color each patch by its id rather than all alike, to follow patches through transitions
+    by_id: bool,
+    // the curve patches move along between layouts
+    easing: Easing,
+}
+
+impl PatchStyle {
+    fn color_for(&self, patch: &Patch) -> Color {
+        if self.by_id {
+            Color {
+                a: self.color.a,
+                ..color_for_id(patch.id)
             }
+        } else if is_turned(patch) {
+            self.rotated_color
+        } else {
+            self.color
         }
+    }
 
-        if is_key_pressed(KeyCode::Escape) {
-            break;
+    // the id label, sized to the patch and left off patches too small to hold it
+    fn draw_label(&self, id: i32, center: Vec2, size: Vec2) {
+        if !self.labels {
+            return;
+        }
+        let text = id.to_string();
+        let font_size = (size.min_element() * 0.4).clamp(10., 32.);
+        let measured = measure_text(&text, None, font_size as u16, 1.0);
+        if measured.width > size.x || measured.height > size.y {
+            return;
         }
+        draw_text(
+            &text,
+            center.x - measured.width / 2.,
+            center.y + measured.height / 2.,
+            font_size,
+            WHITE,
+        );
+    }
+}
+
+fn draw_patches(patches: &[Patch], style: &PatchStyle) {
+    for patch in patches {
+        draw_patch_body(
+            patch.center,
+            patch.unrotated_extent(),
+            patch.rotation,
+            style.textures.get(&patch.id).copied(),
+            style.color_for(patch),
+            style.outline,
+        );
+        style.draw_label(patch.id, patch.center, patch.footprint());
+    }
+}
 
-        clear_background(WHITE);
+// the pages as they're written out, cropped with --trim-atlas
+fn exported_pages(options: &Options, pages: &[Page], config: &PackingConfig) -> Vec<Page> {
+    if options.trim_atlas {
+        trim_pages(pages, config)
+    } else {
+        pages.to_vec()
+    }
+}
 
-        if let Some(last_step_time) = last_step_time {
-            if let Some(previous_state) = &previous_state {
-                let now = get_time();
-                let elapsed = now - last_step_time;
-                draw_interpolated_patches(
-                    previous_state.patches(),
-                    state.patches(),
-                    elapsed as f32,
-                    patch_color,
-                );
-            }
-        } else {
-            draw_patches(state.patches(), patch_color);
+// renders each page's patches, without labels or outlines, into an offscreen target the size of
+// the page and saves it as the png the exported metadata names
+fn write_atlas_images(pages: &[Page], style: &PatchStyle) {
+    for (index, page) in pages.iter().enumerate() {
+        let target = render_target(page.width as u32, page.height as u32);
+        target.texture.set_filter(FilterMode::Nearest);
+        let mut camera = Camera2D::from_display_rect(Rect::new(0., 0., page.width, page.height));
+        camera.render_target = Some(target);
+        set_camera(&camera);
+        clear_background(Color::new(0., 0., 0., 0.));
+        for patch in &page.patches {
+            draw_patch_body(
+                patch.center,
+                patch.unrotated_extent(),
+                patch.rotation,
+                style.textures.get(&patch.id).copied(),
+                style.color_for(patch),
+                None,
+            );
         }
+        set_default_camera();
+
+        let path = output_path(&page_image_name(index, pages.len()));
+        target
+            .texture
+            .get_texture_data()
+            .export_png(&path.to_string_lossy());
+        target.delete();
+    }
+}
 
-        draw_text(state.name(), 20.0, screen_height() - 20., 30.0, DARKGRAY);
+fn draw_interpolated_patches(
+    old_patches: &[Patch],
+    new_patches: &[Patch],
+    elapsed: f32,
+    duration: f32,
+    style: &PatchStyle,
+) {
+    // every patch eases from its own earlier self, so both ends must hold the same patches;
+    // `draw_layout` only interpolates when they do
+    debug_assert_eq!(old_patches.len(), new_patches.len());
+    debug_assert!(
+        same_ids(old_patches, new_patches),
+        "interpolating between layouts of different patches"
+    );
+    let t = ease_unit(elapsed, duration, style.easing);
+    for (old, current) in transition_pairs(old_patches, new_patches) {
+        let (center, extent, rotation) = tweened(old, current, t);
+        draw_patch_body(
+            center,
+            extent,
+            rotation,
+            style.textures.get(&current.id).copied(),
+            style.color_for(current),
+            style.outline,
+        );
+        style.draw_label(current.id, center, extent);
+    }
+}
 
-        next_frame().await
+// each new patch with the old patch it animates from. stages may reorder patches, and patches
+// of the same size are interchangeable to a sort, so they're paired by id rather than position;
+// otherwise same sized siblings would swap places mid-transition
+fn transition_pairs<'a>(old: &'a [Patch], new: &'a [Patch]) -> Vec<(&'a Patch, &'a Patch)> {
+    let old_by_id: HashMap<i32, &Patch> = old.iter().map(|p| (p.id, p)).collect();
+    new.iter().map(|p| (old_by_id[&p.id], p)).collect()
+}
+
+// the center, unrotated size and rotation of a patch `t` of the way from `old` to `new`. sizes
+// are eased before rotation is applied, so a patch turned a quarter turn keeps its shape and
+// visibly rotates rather than squashing through its swapped extent
+fn tweened(old: &Patch, new: &Patch, t: f32) -> (Vec2, Vec2, f32) {
+    let center = old.center + t * (new.center - old.center);
+    let old_extent = old.unrotated_extent();
+    let extent = old_extent + t * (new.unrotated_extent() - old_extent);
+    (center, extent, lerp_angle(old.rotation, new.rotation, t))
+}
+
+// draws a layout, easing in from `transition_from` when a step was taken `elapsed` seconds ago
+fn draw_layout(
+    transition_from: Option<&[Patch]>,
+    patches: &[Patch],
+    elapsed: Option<f32>,
+    duration: f32,
+    style: &PatchStyle,
+) {
+    match (transition_from, elapsed) {
+        (Some(transition_from), Some(elapsed)) if same_ids(transition_from, patches) => {
+            draw_interpolated_patches(transition_from, patches, elapsed, duration, style)
+        }
+        // a layout of other patches, like one loaded in place of the last, has nothing sensible
+        // to animate from, so it's simply drawn
+        _ => draw_patches(patches, style),
+    }
+}
+
+// a camera fitting the whole atlas, centered, into `area` of the screen
+fn fit_camera(config: &PackingConfig, area: Rect) -> Camera2D {
+    let scale = (area.w / config.width).min(area.h / config.height);
+    let origin = Vec2::new(
+        area.x + (area.w - config.width * scale) / 2.,
+        area.y + (area.h - config.height * scale) / 2.,
+    );
+    Camera2D::from_display_rect(Rect::new(
+        -origin.x / scale,
+        -origin.y / scale,
+        screen_width() / scale,
+        screen_height() / scale,
+    ))
+}
+
+// a second walk packing the same patches with another packer, stepped in lockstep with the main
+// walk so the two can be compared side by side
+struct Comparison {
+    walk: PipelineWalk,
+    transition_from: Option<Vec<Patch>>,
+}
+
+impl Comparison {
+    fn new(config: PackingConfig, patches: Vec<Patch>) -> Self {
+        Self {
+            walk: PipelineWalk::new(Pipeline::for_config(&config), config, "Initial", patches),
+            transition_from: None,
+        }
+    }
+}
+
+// parses an aspect ratio like "16:9" as width over height
+fn parse_aspect(aspect: &str) -> Option<f32> {
+    let (width, height) = aspect.split_once(':')?;
+    let (width, height): (f32, f32) = (width.trim().parse().ok()?, height.trim().parse().ok()?);
+    if width > 0. && height > 0. {
+        Some(width / height)
+    } else {
+        None
+    }
+}
+
+// parses a tile size like "32x32"
+fn parse_tile_size(size: &str) -> Option<Vec2> {
+    let (width, height) = size.split_once(['x', 'X'])?;
+    let (width, height): (f32, f32) = (width.trim().parse().ok()?, height.trim().parse().ok()?);
+    if width > 0. && height > 0. {
+        Some(Vec2::new(width, height))
+    } else {
+        None
+    }
+}
+
+// a color of its own for each id: the id is hashed to a hue, so neighboring ids look unalike
+fn color_for_id(id: i32) -> Color {
+    let hash = (id as u32).wrapping_mul(0x9E37_79B9);
+    hsv_to_rgb(hash as f32 / u32::MAX as f32, 0.65, 0.9)
+}
+
+// hue, saturation and value all in 0..=1
+fn hsv_to_rgb(hue: f32, saturation: f32, value: f32) -> Color {
+    let sector = (hue.fract() * 6.).floor();
+    let f = hue.fract() * 6. - sector;
+    let p = value * (1. - saturation);
+    let q = value * (1. - saturation * f);
+    let t = value * (1. - saturation * (1. - f));
+    let (r, g, b) = match sector as i32 {
+        0 => (value, t, p),
+        1 => (q, value, p),
+        2 => (p, value, t),
+        3 => (p, q, value),
+        4 => (t, p, value),
+        _ => (value, p, q),
+    };
+    Color::new(r, g, b, 1.)
+}
+
+// parses "#RRGGBB" or "#RRGGBBAA", with the leading '#' optional
+fn parse_hex_color(hex: &str) -> Option<Color> {
+    let hex = hex.strip_prefix('#').unwrap_or(hex);
+    if (hex.len() != 6 && hex.len() != 8) || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    let alpha = if hex.len() == 8 { channel(6)? } else { 255 };
+    Some(Color::from_rgba(
+        channel(0)?,
+        channel(2)?,
+        channel(4)?,
+        alpha,
+    ))
+}
+
+// the "#RRGGBBAA" form `parse_hex_color` reads
+fn hex_color(color: Color) -> String {
+    let [r, g, b, a]: [u8; 4] = color.into();
+    format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a)
+}
+
+// source pixels per side of each alpha mask cell
+#[cfg(feature = "alpha-mask")]
+const MASK_CELL_SIZE: usize = 8;
+
+// how many earlier seeds Shift+R can step back through
+const SEED_HISTORY: usize = 16;
+
+// how many patches a stress pattern generates without --count
+const DEFAULT_STRESS_COUNT: usize = 100;
+
+// the largest texture most GPUs can be relied on to load
+const DEFAULT_MAX_SIZE: u32 = 4096;
+
+const PATCH_COLOR: Color = Color::new(60. / 255., 60. / 255., 60. / 255., 128. / 255.);
+const ROTATED_PATCH_COLOR: Color = Color::new(30. / 255., 90. / 255., 180. / 255., 128. / 255.);
+
+// applies the oversized policy, reporting what it did, and quits if the policy is to fail
+fn fit_oversized_or_exit(
+    initial: &mut InitialState,
+    config: &PackingConfig,
+    policy: OversizedPolicy,
+) -> Vec<i32> {
+    let oversized = match initial.fit_oversized(config, policy) {
+        Ok(oversized) => oversized,
+        Err(e) => {
+            eprintln!("Oversized patches: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if !oversized.is_empty() {
+        eprintln!(
+            "{} {} oversized patches: {:?}",
+            if policy == OversizedPolicy::Scale {
+                "Scaled down"
+            } else {
+                "Skipped"
+            },
+            oversized.len(),
+            oversized
+        );
+    }
+    oversized
+}
+
+// reads a layout exported elsewhere, reporting what's wrong with it, and quits if it isn't an
+// exported layout at all
+fn import_layout_or_exit(path: &Path) -> ImportedLayout {
+    let imported = std::fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|text| import_atlas_json(&text));
+    match imported {
+        Ok(imported) => {
+            for problem in &imported.problems {
+                eprintln!("{}: {}", path.display(), problem);
+            }
+            imported
+        }
+        Err(e) => {
+            eprintln!("Unable to import {}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+    }
+}
+
+// the pipeline named by --stages, falling back to the default one for `config`; with
+// --alpha-mask, loaded images are packed by their masks instead
+fn options_pipeline(options: &Options, config: &PackingConfig, initial: &InitialState) -> Pipeline {
+    #[cfg(feature = "alpha-mask")]
+    if options.alpha_mask {
+        return Pipeline::for_masks(initial.masks.clone());
+    }
+    #[cfg(not(feature = "alpha-mask"))]
+    let _ = initial;
+    match options.stages.as_deref().map(Pipeline::from_names) {
+        Some(Ok(pipeline)) => pipeline,
+        Some(Err(e)) => {
+            eprintln!("Invalid --stages: {}, using default pipeline", e);
+            Pipeline::for_config(config)
+        }
+        None => Pipeline::for_config(config),
+    }
+}
+
+// with --target-aspect, `config` with its atlas resized to fit `initial` to that aspect,
+// reporting what was chosen; otherwise `config` as it is
+fn aspect_config(
+    options: &Options,
+    config: PackingConfig,
+    initial: &InitialState,
+    pipeline: &Pipeline,
+) -> PackingConfig {
+    let aspect = match options.target_aspect {
+        Some(aspect) => aspect,
+        None => return config,
+    };
+    let fitted = fit_to_aspect(&initial.patches, config, pipeline, aspect);
+    let packed = pipeline.run(initial.patches.clone(), fitted);
+    println!(
+        "Fit to aspect {:.2} with a {} x {} atlas, {:.1}% full",
+        aspect,
+        fitted.width,
+        fitted.height,
+        fill_ratio(&packed) * 100.
+    );
+    fitted
+}
+
+// with --min-area, `config` with its atlas resized to the width packing `initial` into the least
+// area, reporting what was chosen; otherwise `config` as it is
+fn area_config(options: &Options, config: PackingConfig, initial: &InitialState) -> PackingConfig {
+    if !options.min_area {
+        return config;
+    }
+    let fitted = fit_to_area(&initial.patches, config);
+    let packed = pack(fitted, initial.patches.clone());
+    println!(
+        "Swept {} widths, the least area is a {} x {} atlas of {} pixels, {:.1}% full",
+        AREA_SWEEP_STEPS + 1,
+        fitted.width,
+        fitted.height,
+        fitted.width * fitted.height,
+        fill_ratio(&packed) * 100.
+    );
+    fitted
+}
+
+fn options_config(options: &Options, width: f32, height: f32) -> PackingConfig {
+    // a max size sizes each page to it, unless the atlas is asked to be smaller still
+    let (width, height) = match options.max_size {
+        Some(max) => (
+            options
+                .atlas_width
+                .map_or(max as f32, |w| w.min(max as f32)),
+            options
+                .atlas_height
+                .map_or(max as f32, |h| h.min(max as f32)),
+        ),
+        None => (
+            options.atlas_width.unwrap_or(width),
+            options.atlas_height.unwrap_or(height),
+        ),
+    };
+    PackingConfig {
+        width,
+        height,
+        padding: options.padding,
+        border: options.border.unwrap_or(options.padding),
+        packer: options.packer,
+        sort: options.sort,
+        orientation: options.orientation,
+        extrude: options.extrude,
+        probe_columns: options.probe_columns,
+        try_turns: options.try_turns,
+        overlap_epsilon: options.overlap_epsilon,
+        tile: options.uniform,
+        fixed_rows: options.fixed_rows,
+    }
+}
+
+fn initial_state(
+    options: &Options,
+    config: PackingConfig,
+    textures: bool,
+) -> (InitialState, HashMap<i32, Texture2D>) {
+    if let Some(path) = &options.manifest {
+        match InitialState::from_manifest(config, path) {
+            Ok(initial) => return (prepared(options, initial), HashMap::new()),
+            Err(e) => {
+                eprintln!("Unable to read manifest {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        }
+    }
+    let (initial, textures) = match (&options.image_dir, options.stress, options.count) {
+        (Some(dir), _, _) => load_images(config, dir, options.trim, options.align, textures),
+        (None, Some(pattern), count) => (
+            InitialState::stress(config, pattern, count.unwrap_or(DEFAULT_STRESS_COUNT)),
+            HashMap::new(),
+        ),
+        (None, None, Some(count)) => (InitialState::random(config, count), HashMap::new()),
+        (None, None, None) => (
+            InitialState::new(config, options.cols, options.rows, options.shuffle),
+            HashMap::new(),
+        ),
+    };
+    (prepared(options, initial), textures)
+}
+
+// `initial_state` for the window, showing loading progress for loaded images
+async fn initial_state_showing_progress(
+    options: &Options,
+    config: PackingConfig,
+) -> (InitialState, HashMap<i32, Texture2D>) {
+    // a manifest takes precedence over images, as in `initial_state`
+    match (&options.image_dir, &options.manifest) {
+        (Some(dir), None) => {
+            let (initial, textures) = load_images_showing_progress(
+                config,
+                dir,
+                options.trim,
+                options.align,
+                options.background,
+            )
+            .await;
+            (prepared(options, initial), textures)
+        }
+        _ => initial_state(options, config, true),
+    }
+}
+
+// the patches to pack, aligned, grouped and tilted as the options ask
+fn prepared(options: &Options, mut initial: InitialState) -> InitialState {
+    initial.align(options.align);
+    if options.group_frames {
+        initial.group_frames();
+    }
+    #[cfg(feature = "free-rotation")]
+    initial.tilt(options.max_tilt);
+    initial
+}
+
+// packs the whole pipeline to completion without a window and writes the atlas, for CI; the
+// atlas defaults to the window's size so a headless atlas matches an interactive one
+fn run_headless(options: &Options) {
+    export_headless(options);
+    if let Some(mut watch) = options_watch(options) {
+        while watch.wait_for_change() {
+            export_headless(options);
+        }
+    }
+}
+
+// with --watch, a watch on the image directory; watching needs one to watch
+fn options_watch(options: &Options) -> Option<DirectoryWatch> {
+    if !options.watch {
+        return None;
+    }
+    match &options.image_dir {
+        Some(dir) => match DirectoryWatch::new(dir) {
+            Ok(watch) => {
+                println!("Watching {} for changes", dir.display());
+                Some(watch)
+            }
+            Err(e) => {
+                eprintln!("Unable to watch {}: {}, ignoring --watch", dir.display(), e);
+                None
+            }
+        },
+        None => {
+            eprintln!("--watch needs --images to watch, ignoring it");
+            None
+        }
+    }
+}
+
+// with --stats, writes how well `pages` packed, measured before any trimming like the HUD
+fn write_stats(options: &Options, pages: &[Page], config: &PackingConfig, steps: &[Step]) {
+    if let Some(path) = &options.stats {
+        log_info!(
+            "writing stats for {} pages to {}",
+            pages.len(),
+            path.display()
+        );
+        match std::fs::write(path, export_stats(pages, config, steps)) {
+            Ok(()) => println!("Wrote {}", path.display()),
+            Err(e) => eprintln!("Unable to write {}: {}", path.display(), e),
+        }
+    }
+}
+
+fn export_headless(options: &Options) {
+    let window = conf();
+    let config = options_config(
+        options,
+        window.window_width as f32,
+        window.window_height as f32,
+    );
+    let (mut initial, _) = initial_state(options, config, false);
+    fit_oversized_or_exit(&mut initial, &config, options.on_oversized);
+    let pipeline = options_pipeline(options, &config, &initial);
+    let config = aspect_config(options, config, &initial, &pipeline);
+    let config = area_config(options, config, &initial);
+    log_packing(&initial, &config);
+    let (packed, pipeline, steps) = if options.stats.is_some() {
+        // the stats time each stage, so the pipeline is walked a stage at a time
+        let mut walk = PipelineWalk::new(pipeline, config, "Initial", initial.patches);
+        while walk.advance().is_some() {}
+        let packed = walk.current().patches.clone();
+        (packed, walk.pipeline, walk.steps)
+    } else {
+        let packed = pipeline.run(initial.patches, config);
+        (packed, pipeline, Vec::new())
+    };
+    let pages = paginate(packed, config, &pipeline);
+    log_info!("packed onto {} pages", pages.len());
+    write_stats(options, &pages, &config, &steps);
+    // paging only leaves patches hanging off the last page when they fit on no page at all
+    if let Some(overflow) = pages
+        .last()
+        .and_then(|page| vertical_overflow(&page.patches, config.height))
+    {
+        eprintln!(
+            "{} patches overflow the atlas by {:.0}px: {:?}",
+            overflow.ids.len(),
+            overflow.amount,
+            overflow.ids
+        );
+    }
+
+    let path = output_path(options.format.file_name());
+    let pages = exported_pages(options, &pages, &config);
+    if let Err(e) = write_atlas(&path, &pages, &config, &initial.names, options.format) {
+        eprintln!("Unable to write {}: {}", path.display(), e);
+        std::process::exit(1);
+    }
+    println!("Wrote {} ({} pages)", path.display(), pages.len());
+}
+
+// what's about to be packed and how, for following a run from its --verbose output
+fn log_packing(initial: &InitialState, config: &PackingConfig) {
+    log_info!(
+        "packing {} patches into {}x{} with {}, sorted by {}",
+        initial.patches.len(),
+        config.width,
+        config.height,
+        config.packer.name(),
+        config.sort.name()
+    );
+}
+
+fn main() {
+    let options = Options::parse(std::env::args().skip(1));
+    log::set_verbosity(options.verbose);
+    if options.dump_config {
+        print!("{}", options.preset_json());
+        return;
+    }
+    // without --seed, start from the clock; either way the seed is shown so a layout can be
+    // reproduced later
+    let seed = options.seed.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_nanos() as u64)
+    });
+    rand::srand(seed);
+    if options.headless {
+        run_headless(&options);
+    } else {
+        macroquad::Window::from_config(conf(), run_interactive(options, seed));
+    }
+}
+
+async fn run_interactive(options: Options, mut seed: u64) {
+    let mut config = options_config(&options, screen_width(), screen_height());
+    let imported = options.import.as_deref().map(import_layout_or_exit);
+    if let Some(imported) = &imported {
+        config.width = imported.width;
+        config.height = imported.height;
+    }
+    let (mut initial, textures) = initial_state_showing_progress(&options, config).await;
+    let mut oversized = fit_oversized_or_exit(&mut initial, &config, options.on_oversized);
+    let mut walk = match &imported {
+        Some(imported) => {
+            // an imported layout is already packed, so there are no stages to run; restarting
+            // repacks its patches
+            initial.patches = imported.patches.clone();
+            initial.names = imported.names.clone();
+            oversized.clear();
+            PipelineWalk::new(
+                Pipeline { stages: Vec::new() },
+                config,
+                "Imported",
+                initial.patches.clone(),
+            )
+        }
+        None => {
+            let pipeline = options_pipeline(&options, &config, &initial);
+            config = aspect_config(&options, config, &initial, &pipeline);
+            config = area_config(&options, config, &initial);
+            log_packing(&initial, &config);
+            PipelineWalk::new(pipeline, config, "Initial", initial.patches.clone())
+        }
+    };
+    let import_problems = imported.map_or_else(Vec::new, |imported| imported.problems);
+    let mut transition_from: Option<Vec<Patch>> = None;
+    let mut last_step_time = None;
+    let mut overlaps = terminal_overlaps(&walk);
+    let mut pages = walk_pages(&walk);
+    let mut page_index = 0;
+    let mut patch_style = PatchStyle {
+        color: options.patch_color,
+        rotated_color: ROTATED_PATCH_COLOR,
+        outline: None,
+        textures,
+        labels: true,
+        by_id: false,
+        easing: options.easing,
+    };
+    let mut view = View::new();
+    let mut show_padding = false;
+    let mut show_grid = false;
+    let mut show_free_rects = false;
+    let mut explode = Explode::new();
+    // seeds replaced by regenerating, most recent last
+    let mut previous_seeds: VecDeque<u64> = VecDeque::new();
+    let mut auto_advance = false;
+    let mut comparison: Option<Comparison> = None;
+    // patches clicked to turn them the other way from how they were uprighted
+    let mut turned: HashSet<i32> = HashSet::new();
+    let mut last_mouse_position = Vec2::from(mouse_position());
+    let mut last_screen_size = Vec2::new(screen_width(), screen_height());
+    let mut panel = ControlPanel { visible: true };
+    if options.export_on_exit.is_some() {
+        prevent_quit();
+    }
+    let mut watch = options_watch(&options);
+
+    loop {
+        let mouse = Vec2::from(mouse_position());
+        let (_, wheel) = mouse_wheel();
+        if wheel != 0. {
+            view.zoom_about(&walk.config, mouse, 1.1f32.powf(wheel.signum()));
+        }
+        if is_mouse_button_down(MouseButton::Middle) {
+            view.pan += mouse - last_mouse_position;
+        }
+        last_mouse_position = mouse;
+
+        let clicked = panel.clicked(mouse);
+        let triggered =
+            |command: Command| is_key_pressed(command.key()) || clicked == Some(command);
+
+        if triggered(Command::Panel) {
+            panel.visible = !panel.visible;
+        }
+
+        if triggered(Command::ResetView) {
+            view = View::new();
+        }
+
+        if triggered(Command::Padding) {
+            show_padding = !show_padding;
+        }
+
+        if triggered(Command::Grid) {
+            show_grid = !show_grid;
+        }
+
+        if triggered(Command::Outlines) {
+            patch_style.outline = match patch_style.outline {
+                Some(_) => None,
+                None => Some(options.outline_color),
+            };
+        }
+
+        if triggered(Command::IdColors) {
+            patch_style.by_id = !patch_style.by_id;
+        }
+
+        if triggered(Command::Labels) {
+            patch_style.labels = !patch_style.labels;
+        }
+
+        if triggered(Command::FreeRects) {
+            show_free_rects = !show_free_rects;
+        }
+
+        if triggered(Command::Explode) {
+            explode.toggle(get_time());
+        }
+
+        if triggered(Command::Easing) {
+            patch_style.easing = patch_style.easing.cycled();
+            println!("Easing transitions with {}", patch_style.easing.name());
+        }
+
+        if triggered(Command::AutoAdvance) {
+            auto_advance = !auto_advance;
+        }
+
+        // auto-advance waits for the previous transition to finish easing before stepping again
+        let transition_done =
+            last_step_time.is_none_or(|t| get_time() - t >= options.anim_speed as f64);
+        if triggered(Command::Step) || (auto_advance && transition_done) {
+            // in a comparison the walks can differ in length, so keep going until both finish
+            let compared = comparison.as_mut().map(|comparison| {
+                comparison.transition_from = comparison.walk.advance();
+                comparison.transition_from.is_some()
+            });
+            if let Some(previous) = walk.advance() {
+                transition_from = Some(previous);
+                last_step_time = Some(get_time());
+                overlaps = terminal_overlaps(&walk);
+                pages = walk_pages(&walk);
+                page_index = 0;
+            } else if compared == Some(true) {
+                transition_from = None;
+                last_step_time = Some(get_time());
+            } else {
+                auto_advance = false;
+            }
+        }
+
+        // 1 jumps back to the starting layout, 2 and up to the layout after each stage in turn
+        let jump = [
+            KeyCode::Key1,
+            KeyCode::Key2,
+            KeyCode::Key3,
+            KeyCode::Key4,
+            KeyCode::Key5,
+            KeyCode::Key6,
+            KeyCode::Key7,
+            KeyCode::Key8,
+            KeyCode::Key9,
+        ]
+        .iter()
+        .position(|&key| is_key_pressed(key));
+        if let Some(step) = jump {
+            if let Some(comparison) = &mut comparison {
+                comparison.transition_from = comparison.walk.jump_to(step);
+            }
+            if let Some(previous) = walk.jump_to(step) {
+                transition_from = Some(previous);
+                last_step_time = Some(get_time());
+                overlaps = terminal_overlaps(&walk);
+                pages = walk_pages(&walk);
+                page_index = 0;
+            }
+        }
+
+        if triggered(Command::Back) {
+            let compared = comparison.as_mut().map(|comparison| {
+                comparison.transition_from = comparison.walk.back();
+                comparison.transition_from.is_some()
+            });
+            if let Some(previous) = walk.back() {
+                transition_from = Some(previous);
+                last_step_time = Some(get_time());
+                overlaps = terminal_overlaps(&walk);
+                pages = walk_pages(&walk);
+                page_index = 0;
+            } else if compared == Some(true) {
+                transition_from = None;
+                last_step_time = Some(get_time());
+            }
+        }
+
+        let mut restart = false;
+        let mut reloaded = false;
+        if watch.as_mut().is_some_and(DirectoryWatch::settled_change) {
+            let (mut reloaded_initial, textures) = initial_state(&options, config, true);
+            oversized = fit_oversized_or_exit(&mut reloaded_initial, &config, options.on_oversized);
+            initial = reloaded_initial;
+            patch_style.textures = textures;
+            restart = true;
+            reloaded = true;
+        }
+        if triggered(Command::CyclePacker) {
+            config.packer = config.packer.cycled();
+            restart = true;
+        }
+        if triggered(Command::Repack) {
+            // straight to the next packer's finished layout, easing over from what's shown now
+            config.packer = config.packer.cycled();
+            turned.clear();
+            log_info!("repacking with {}", config.packer.name());
+            let from = walk.current().patches.clone();
+            walk = PipelineWalk::new(
+                options_pipeline(&options, &config, &initial),
+                config,
+                "Initial",
+                initial.patches.clone(),
+            );
+            while walk.advance().is_some() {}
+            transition_from = Some(from);
+            last_step_time = Some(get_time());
+            overlaps = terminal_overlaps(&walk);
+            pages = walk_pages(&walk);
+            page_index = 0;
+        }
+        // clicking a patch turns it the other way and repacks the rest around it, easing over
+        // from what's shown now; clicking it again turns it back
+        if is_mouse_button_pressed(MouseButton::Left) && clicked.is_none() && comparison.is_none() {
+            let spread = explode.amount(get_time(), options.anim_speed) * options.explode_factor;
+            let shown = exploded(&pages[page_index].patches, &walk.config, spread);
+            let point = view.screen_to_world(&walk.config, mouse);
+            if let Some(id) = patch_at(&shown, point).map(|p| p.id) {
+                if !turned.remove(&id) {
+                    turned.insert(id);
+                }
+                log_info!("turning #{}, repacking with {} turned", id, turned.len());
+                let from = walk.current().patches.clone();
+                walk = PipelineWalk::new(
+                    options_pipeline(&options, &config, &initial).keeping_turns(),
+                    config,
+                    "Turned",
+                    with_turns_toggled(initial.patches.clone(), config, &turned),
+                );
+                while walk.advance().is_some() {}
+                transition_from = Some(from);
+                last_step_time = Some(get_time());
+                overlaps = terminal_overlaps(&walk);
+                pages = walk_pages(&walk);
+                page_index = 0;
+            }
+        }
+        if triggered(Command::CycleSort) {
+            config.sort = config.sort.cycled();
+            restart = true;
+        }
+        if triggered(Command::Regenerate) {
+            if options.image_dir.is_some() || options.manifest.is_some() {
+                eprintln!("Loaded sizes can't be regenerated");
+            } else {
+                // Shift+R steps back to the seed before, R rolls a new one
+                let next = if is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift) {
+                    previous_seeds.pop_back()
+                } else {
+                    previous_seeds.push_back(seed);
+                    if previous_seeds.len() > SEED_HISTORY {
+                        previous_seeds.pop_front();
+                    }
+                    Some(rand::rand() as u64)
+                };
+                match next {
+                    Some(next) => {
+                        seed = next;
+                        rand::srand(seed);
+                        initial = initial_state(&options, config, true).0;
+                        oversized =
+                            fit_oversized_or_exit(&mut initial, &config, options.on_oversized);
+                        restart = true;
+                    }
+                    None => eprintln!("No earlier seed to go back to"),
+                }
+            }
+        }
+        if triggered(Command::Compare) {
+            // both sides start over together so their steps line up
+            comparison = match comparison {
+                Some(_) => None,
+                None => Some(Comparison::new(
+                    PackingConfig {
+                        packer: options.compare,
+                        ..config
+                    },
+                    initial.patches.clone(),
+                )),
+            };
+            restart = true;
+        }
+        let screen_size = Vec2::new(screen_width(), screen_height());
+        if screen_size != last_screen_size {
+            // the view refits itself to the new window; repacking also resizes the atlas to it,
+            // unless the atlas was sized some other way
+            view.resized(&walk.config, last_screen_size, screen_size);
+            last_screen_size = screen_size;
+            let sized_elsewhere =
+                options.import.is_some() || options.target_aspect.is_some() || options.min_area;
+            if options.repack_on_resize && !sized_elsewhere {
+                let resized = options_config(&options, screen_size.x, screen_size.y);
+                if (resized.width, resized.height) != (config.width, config.height) {
+                    config.width = resized.width;
+                    config.height = resized.height;
+                    restart = true;
+                }
+            }
+        }
+        if restart {
+            turned.clear();
+            log_packing(&initial, &config);
+            if let Some(comparison) = &mut comparison {
+                *comparison = Comparison::new(
+                    PackingConfig {
+                        packer: comparison.walk.config.packer,
+                        ..config
+                    },
+                    initial.patches.clone(),
+                );
+            }
+            // restart from the same patches with the new packer or sort, through the same
+            // --stages or --alpha-mask pipeline as the first layout
+            walk = PipelineWalk::new(
+                options_pipeline(&options, &config, &initial),
+                config,
+                "Initial",
+                initial.patches.clone(),
+            );
+            transition_from = None;
+            last_step_time = None;
+            overlaps = terminal_overlaps(&walk);
+            pages = walk_pages(&walk);
+            page_index = 0;
+        }
+        if reloaded {
+            // jump straight to the finished layout and export it, as a headless watch would
+            while walk.advance().is_some() {}
+            if let Some(comparison) = &mut comparison {
+                while comparison.walk.advance().is_some() {}
+            }
+            overlaps = terminal_overlaps(&walk);
+            pages = walk_pages(&walk);
+            write_stats(&options, &pages, &walk.config, &walk.steps);
+            let path = output_path(options.format.file_name());
+            let exported = exported_pages(&options, &pages, &walk.config);
+            match write_atlas(
+                &path,
+                &exported,
+                &walk.config,
+                &initial.names,
+                options.format,
+            ) {
+                Ok(()) => println!("Wrote {} ({} pages)", path.display(), pages.len()),
+                Err(e) => eprintln!("Unable to write {}: {}", path.display(), e),
+            }
+        }
+
+        if triggered(Command::PreviousPage) {
+            page_index = (page_index + pages.len() - 1) % pages.len();
+        }
+        if triggered(Command::NextPage) {
+            page_index = (page_index + 1) % pages.len();
+        }
+
+        if triggered(Command::Export) {
+            write_stats(&options, &pages, &walk.config, &walk.steps);
+            let path = output_path(options.format.file_name());
+            let exported = exported_pages(&options, &pages, &walk.config);
+            if let Err(e) = write_atlas(
+                &path,
+                &exported,
+                &walk.config,
+                &initial.names,
+                options.format,
+            ) {
+                eprintln!("Unable to write {}: {}", path.display(), e);
+            }
+        }
+
+        if triggered(Command::ExportImages) {
+            write_atlas_images(
+                &exported_pages(&options, &pages, &walk.config),
+                &patch_style,
+            );
+        }
+
+        if triggered(Command::SaveSession) {
+            let path = output_path("session.json");
+            log_info!("saving the session to {}", path.display());
+            if let Err(e) = save_session(&path, &walk.current().patches, &walk.config) {
+                eprintln!("Unable to save session to {}: {}", path.display(), e);
+            }
+        }
+
+        if triggered(Command::PrintTable) {
+            // the exact numbers behind the current layout, rotation in degrees
+            println!(
+                "{}\n{}",
+                walk.current().name,
+                patch_table(&walk.current().patches)
+            );
+        }
+
+        if triggered(Command::LoadSession) {
+            let path = output_path("session.json");
+            match load_session(&path) {
+                Ok((patches, loaded_config)) => {
+                    log_info!("loaded {} patches from {}", patches.len(), path.display());
+                    // a loaded layout is already packed, so there are no stages left to run;
+                    // as with --import, repacking or turning a patch repacks its patches
+                    comparison = None;
+                    turned.clear();
+                    config = loaded_config;
+                    initial.patches = patches.clone();
+                    initial.names = HashMap::new();
+                    oversized.clear();
+                    walk = PipelineWalk::new(
+                        Pipeline { stages: Vec::new() },
+                        config,
+                        "Loaded",
+                        patches,
+                    );
+                    transition_from = None;
+                    last_step_time = None;
+                    overlaps = terminal_overlaps(&walk);
+                    pages = walk_pages(&walk);
+                    page_index = 0;
+                }
+                Err(e) => eprintln!("Unable to load session from {}: {}", path.display(), e),
+            }
+        }
+
+        // with --export-on-exit, closing the window waits for the layout to be written
+        if triggered(Command::Quit) || is_quit_requested() {
+            if let Some(path) = &options.export_on_exit {
+                while walk.advance().is_some() {}
+                let pages = walk_pages(&walk);
+                write_stats(&options, &pages, &walk.config, &walk.steps);
+                let exported = exported_pages(&options, &pages, &walk.config);
+                match write_atlas(
+                    path,
+                    &exported,
+                    &walk.config,
+                    &initial.names,
+                    options.format,
+                ) {
+                    Ok(()) => println!("Wrote {} ({} pages)", path.display(), pages.len()),
+                    Err(e) => eprintln!("Unable to write {}: {}", path.display(), e),
+                }
+            }
+            break;
+        }
+
+        let current = walk.current();
+        let multi_page = pages.len() > 1;
+        let displayed = &pages[page_index].patches;
+        let elapsed = last_step_time.map(|t| (get_time() - t) as f32);
+        let overflow = vertical_overflow(displayed, walk.config.height);
+        clear_background(options.background);
+
+        if let Some(comparison) = &comparison {
+            // each side gets half the window, with a row beneath it for its label
+            let half_width = screen_width() / 2.;
+            let sides = [
+                (&walk, transition_from.as_deref()),
+                (&comparison.walk, comparison.transition_from.as_deref()),
+            ];
+            for (i, &(side, side_from)) in sides.iter().enumerate() {
+                let area = Rect::new(half_width * i as f32, 0., half_width, screen_height() - 40.);
+                set_camera(&fit_camera(&side.config, area));
+                draw_rectangle_lines(0., 0., side.config.width, side.config.height, 2., LIGHTGRAY);
+                draw_layout(
+                    side_from,
+                    &side.current().patches,
+                    elapsed,
+                    options.anim_speed,
+                    &patch_style,
+                );
+
+                set_default_camera();
+                draw_text(
+                    format!(
+                        "{}: {}, {:.1}% filled",
+                        side.config.packer.name(),
+                        side.current().name,
+                        fill_ratio(&side.current().patches) * 100.
+                    )
+                    .as_str(),
+                    area.x + 20.,
+                    screen_height() - 20.,
+                    30.0,
+                    DARKGRAY,
+                );
+            }
+            draw_line(half_width, 0., half_width, screen_height(), 2., GRAY);
+
+            next_frame().await;
+            continue;
+        }
+
+        set_camera(&view.camera(&walk.config));
+        let spread = explode.amount(get_time(), options.anim_speed) * options.explode_factor;
+        let spread_out = |patches: &[Patch]| exploded(patches, &walk.config, spread);
+        let shown = spread_out(displayed);
+        if multi_page {
+            // pages are only split off a finished layout, so there's nothing to animate
+            draw_patches(&shown, &patch_style);
+        } else {
+            let from = transition_from.as_deref().map(spread_out);
+            draw_layout(
+                from.as_deref(),
+                &spread_out(&current.patches),
+                elapsed,
+                options.anim_speed,
+                &patch_style,
+            );
+        }
+
+        if show_padding {
+            draw_padding(displayed, walk.config.padding, ORANGE);
+        }
+
+        let (bounds_min, bounds_max) = bounding_box(displayed);
+        let bounds_size = bounds_max - bounds_min;
+        draw_rectangle_lines(
+            bounds_min.x,
+            bounds_min.y,
+            bounds_size.x,
+            bounds_size.y,
+            2.,
+            SKYBLUE,
+        );
+
+        if show_grid {
+            draw_grid(&walk.config, options.grid_spacing);
+        }
+
+        if show_free_rects {
+            draw_free_rects(&current.free_rects);
+        }
+
+        if let Some(overflow) = &overflow {
+            for patch in displayed.iter().filter(|p| overflow.ids.contains(&p.id)) {
+                draw_rectangle_lines(
+                    patch.left(),
+                    patch.top(),
+                    patch.width(),
+                    patch.height(),
+                    3.,
+                    RED,
+                );
+            }
+        }
+
+        // hit tested where patches are drawn, but described where they're packed
+        let hovered =
+            patch_at(&shown, view.screen_to_world(&walk.config, mouse)).and_then(|hovered| {
+                displayed
+                    .iter()
+                    .find(|p| p.id == hovered.id)
+                    .zip(Some(hovered))
+            });
+        if let Some((_, drawn)) = hovered {
+            draw_rotated_rectangle(
+                drawn.center,
+                drawn.unrotated_extent(),
+                drawn.rotation,
+                Color::new(1., 1., 1., 0.4),
+            );
+        }
+
+        set_default_camera();
+
+        panel.draw(
+            &match (&options.image_dir, &options.manifest) {
+                (None, None) => format!("{}, seed {}", current.name, seed),
+                _ => current.name.to_string(),
+            },
+            mouse,
+        );
+
+        if let Some((patch, _)) = hovered {
+            draw_tooltip(
+                &[
+                    format!("#{}", patch.id),
+                    format!("at {:.1}, {:.1}", patch.left(), patch.top()),
+                    format!("{:.1} x {:.1}", patch.width(), patch.height()),
+                    format!("rotated {:.1} degrees", patch.rotation.to_degrees()),
+                ],
+                mouse,
+            );
+        }
+
+        if show_padding {
+            let atlas_area = walk.config.width * walk.config.height;
+            draw_text(
+                format!(
+                    "padding uses {:.1}% of the atlas",
+                    padding_area(displayed, walk.config.padding) / atlas_area * 100.
+                )
+                .as_str(),
+                20.0,
+                30.,
+                30.0,
+                ORANGE,
+            );
+        }
+
+        if options.image_dir.is_none() && options.manifest.is_none() {
+            let label = format!("seed {}", seed);
+            let label_width = measure_text(&label, None, 30, 1.0).width;
+            draw_text(&label, screen_width() - label_width - 20., 30., 30.0, GRAY);
+        }
+
+        // how long each stage so far took, to show where packing time goes as patch counts grow
+        for (i, pair) in walk.steps.windows(2).enumerate() {
+            let label = format!(
+                "{} -> {}: {:.2}ms",
+                pair[0].name,
+                pair[1].name,
+                pair[1].elapsed.as_secs_f64() * 1000.
+            );
+            let label_width = measure_text(&label, None, 20, 1.0).width;
+            draw_text(
+                &label,
+                screen_width() - label_width - 20.,
+                60. + 20. * i as f32,
+                20.0,
+                GRAY,
+            );
+        }
+
+        if !oversized.is_empty() && options.on_oversized == OversizedPolicy::Skip {
+            draw_text(
+                format!("{} oversized patches skipped", oversized.len()).as_str(),
+                20.0,
+                60.,
+                30.0,
+                RED,
+            );
+        }
+
+        if let Some(overflow) = &overflow {
+            draw_text(
+                format!(
+                    "{} patches overflow the atlas by {:.0}px",
+                    overflow.ids.len(),
+                    overflow.amount
+                )
+                .as_str(),
+                20.0,
+                120.,
+                30.0,
+                RED,
+            );
+        }
+
+        if let Some(problem) = import_problems.first() {
+            draw_text(
+                format!("{} import problems: {}", import_problems.len(), problem).as_str(),
+                20.0,
+                90.,
+                30.0,
+                RED,
+            );
+        }
+
+        draw_text(current.name, 20.0, screen_height() - 20., 30.0, DARKGRAY);
+        let name_width = measure_text(current.name, None, 30, 1.0).width;
+        draw_text(
+            if multi_page {
+                format!(
+                    "{:.1}% filled, {}x{} used, page {} of {} ([ and ] to flip)",
+                    fill_ratio(displayed) * 100.,
+                    bounds_size.x,
+                    bounds_size.y,
+                    page_index + 1,
+                    pages.len()
+                )
+            } else {
+                format!(
+                    "{:.1}% filled, {}x{} used",
+                    fill_ratio(displayed) * 100.,
+                    bounds_size.x,
+                    bounds_size.y
+                )
+            }
+            .as_str(),
+            40.0 + name_width,
+            screen_height() - 20.,
+            30.0,
+            GRAY,
+        );
+        // counted over every page, so a directory of unknown size shows how much there is to pack
+        let totals = format!(
+            "{} patches, total sprite area {:.0} px²",
+            current.patches.len(),
+            sprite_area(&current.patches)
+        );
+        let totals_width = measure_text(&totals, None, 20, 1.0).width;
+        draw_text(
+            &totals,
+            screen_width() - totals_width - 20.,
+            screen_height() - 50.,
+            20.0,
+            GRAY,
+        );
+        if auto_advance {
+            let label = "auto-advancing";
+            let label_width = measure_text(label, None, 30, 1.0).width;
+            draw_text(
+                label,
+                screen_width() - label_width - 20.,
+                screen_height() - 20.,
+                30.0,
+                GRAY,
+            );
+        }
+        if let Some(overlaps) = &overlaps {
+            draw_text(
+                format!("{} overlaps", overlaps.len()).as_str(),
+                20.0,
+                screen_height() - 50.,
+                30.0,
+                RED,
+            );
+
+            let (pot_width, pot_height) = snap_pot(displayed);
+            let max_size = options.max_size.unwrap_or(DEFAULT_MAX_SIZE);
+            let exceeds_max = pot_width > max_size || pot_height > max_size;
+            draw_text(
+                if exceeds_max {
+                    format!(
+                        "{}x{} POT exceeds max size {}",
+                        pot_width, pot_height, max_size
+                    )
+                } else {
+                    format!("{}x{} POT", pot_width, pot_height)
+                }
+                .as_str(),
+                20.0,
+                screen_height() - 80.,
+                30.0,
+                if exceeds_max { RED } else { DARKGRAY },
+            );
+        }
+
+        next_frame().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "watch")]
+    #[test]
+    fn a_watch_reports_pngs_written_to_its_directory_once_settled() {
+        let dir = std::env::temp_dir().join("texture_packer_watch_test");
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut watch = DirectoryWatch::new(&dir).unwrap();
+        assert!(!watch.settled_change());
+
+        std::fs::write(dir.join("notes.txt"), "not an image").unwrap();
+        Image::gen_image_color(4, 4, RED).export_png(&dir.join("new.png").to_string_lossy());
+        let started = Instant::now();
+        let mut changed = false;
+        while !changed && started.elapsed() < WATCH_DEBOUNCE * 8 {
+            std::thread::sleep(Duration::from_millis(50));
+            changed = watch.settled_change();
+        }
+        std::fs::remove_dir_all(&dir).ok();
+        assert!(changed);
+    }
+
+    #[test]
+    fn loaded_image_names_reach_the_exports_of_the_finished_layout() {
+        let dir = std::env::temp_dir().join("texture_packer_names_test");
+        std::fs::create_dir_all(&dir).unwrap();
+        let images = vec![("hero", 12, 20), ("tree, tall", 16, 30), ("rock", 8, 8)];
+        for &(stem, width, height) in &images {
+            Image::gen_image_color(width, height, RED)
+                .export_png(&dir.join(format!("{}.png", stem)).to_string_lossy());
+        }
+        let config = options_config(&Options::default(), 256., 256.);
+        let (initial, _) = load_images(config, &dir, false, 1, false);
+        std::fs::remove_dir_all(&dir).ok();
+
+        let mut walk = PipelineWalk::new(
+            Pipeline::for_config(&config),
+            config,
+            "Initial",
+            initial.patches.clone(),
+        );
+        while walk.advance().is_some() {}
+        let pages = walk_pages(&walk);
+        assert_eq!(pages.len(), 1);
+
+        // the json names each frame by its file, the libgdx atlas each region by its stem
+        let json = export_atlas_json(&pages, &config, &initial.names);
+        let document = json::parse(&json).unwrap();
+        let mut names: Vec<&str> = document
+            .get("frames")
+            .and_then(json::Value::as_array)
+            .unwrap()
+            .iter()
+            .filter_map(|frame| frame.get("name").and_then(json::Value::as_str))
+            .collect();
+        names.sort_unstable();
+        assert_eq!(names, vec!["hero.png", "rock.png", "tree, tall.png"]);
+
+        let atlas = export_libgdx_atlas(&pages[0].patches, &config, &initial.names, "atlas.png");
+        for &(stem, _, _) in &images {
+            assert!(atlas.lines().any(|line| line == stem), "{} missing", stem);
+        }
+        assert!(!atlas.contains("sprite_"));
+    }
+
+    #[test]
+    fn parses_six_and_eight_digit_hex_colors() {
+        let color = parse_hex_color("#ff8000").unwrap();
+        assert_eq!(
+            (color.r, color.g, color.b, color.a),
+            (1., 128. / 255., 0., 1.)
+        );
+
+        let color = parse_hex_color("3C3C3C80").unwrap();
+        assert_eq!(
+            (color.r, color.g, color.b, color.a),
+            (60. / 255., 60. / 255., 60. / 255., 128. / 255.)
+        );
+    }
+
+    #[test]
+    fn quarter_turns_tween_as_rotations() {
+        let old = Patch {
+            id: 0,
+            center: Vec2::new(50., 50.),
+            extent: Vec2::new(40., 10.),
+            rotation: 0.,
+            source_extent: Vec2::new(40., 10.),
+            source_rotation: 0.,
+            source_offset: Vec2::ZERO,
+            group: None,
+        };
+        let turned = Patch {
+            extent: Vec2::new(10., 40.),
+            rotation: std::f32::consts::FRAC_PI_2,
+            ..old
+        };
+
+        for t in [0., 0.25, 0.5, 1.] {
+            let (center, extent, rotation) = tweened(&old, &turned, t);
+            assert_eq!(center, old.center);
+            assert_eq!(extent, Vec2::new(40., 10.));
+            assert!((rotation - t * std::f32::consts::FRAC_PI_2).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn ids_get_stable_distinct_colors() {
+        let rgb = |color: Color| (color.r, color.g, color.b);
+        assert_eq!(rgb(hsv_to_rgb(0., 1., 1.)), (1., 0., 0.));
+        assert_eq!(rgb(hsv_to_rgb(1. / 3., 1., 1.)), (0., 1., 0.));
+        assert_eq!(rgb(hsv_to_rgb(0.5, 0., 0.5)), (0.5, 0.5, 0.5));
+
+        assert_eq!(rgb(color_for_id(12)), rgb(color_for_id(12)));
+        for id in 0..32 {
+            assert_ne!(rgb(color_for_id(id)), rgb(color_for_id(id + 1)));
+        }
+    }
+
+    #[test]
+    fn every_easing_runs_from_zero_to_one() {
+        let mut easing = Easing::Linear;
+        loop {
+            assert!(
+                easing.apply(0.).abs() < 1e-6,
+                "{} starts off zero",
+                easing.name()
+            );
+            assert!(
+                (easing.apply(1.) - 1.).abs() < 1e-6,
+                "{} ends off one",
+                easing.name()
+            );
+            assert_eq!(Easing::from_name(easing.name()), Some(easing));
+            easing = easing.cycled();
+            if easing == Easing::Linear {
+                break;
+            }
+        }
+        assert_eq!(Easing::CubicInOut.apply(0.5), 0.5);
+        assert_eq!(ease_unit(3., 2., Easing::QuadOut), 1.);
+    }
+
+    #[test]
+    fn same_sized_patches_animate_from_their_own_positions_through_a_sort() {
+        // alternating sizes, so sorting by height moves the tall ones ahead of the short ones
+        let patches: Vec<Patch> = (0..8)
+            .map(|i| {
+                let height = if i % 2 == 0 { 10. } else { 20. };
+                Patch {
+                    id: i,
+                    center: Vec2::ZERO,
+                    extent: Vec2::new(10., height),
+                    rotation: 0.,
+                    source_extent: Vec2::new(10., height),
+                    source_rotation: 0.,
+                    source_offset: Vec2::ZERO,
+                    group: None,
+                }
+                .with_left_and_top(i as f32 * 12., 0.)
+            })
+            .collect();
+        let config = PackingConfig {
+            width: 256.,
+            height: 256.,
+            ..options_config(&Options::default(), 256., 256.)
+        };
+        let mut walk = PipelineWalk::new(
+            Pipeline::from_names("sort-height,shelf").unwrap(),
+            config,
+            "Initial",
+            patches,
+        );
+        let before_sort = walk.advance().unwrap();
+        let sorted = walk.current().patches.clone();
+        assert!(before_sort.iter().zip(&sorted).any(|(a, b)| a.id != b.id));
+
+        for (old, new) in transition_pairs(&before_sort, &sorted) {
+            assert_eq!(old.id, new.id);
+            let own = before_sort.iter().find(|p| p.id == new.id).unwrap();
+            assert_eq!(tweened(old, new, 0.).0, own.center);
+        }
+        let before_pack = walk.current().patches.clone();
+        walk.advance().unwrap();
+        for (old, new) in transition_pairs(&before_pack, &walk.current().patches) {
+            assert_eq!(old.id, new.id);
+            assert_eq!(tweened(old, new, 1.).0, new.center);
+        }
+    }
+
+    #[test]
+    fn panel_buttons_name_distinct_keys() {
+        let keys: Vec<&str> = Command::PANEL.iter().map(|c| c.key_name()).collect();
+        for (i, key) in keys.iter().enumerate() {
+            assert_ne!(*key, "?");
+            assert!(!keys[i + 1..].contains(key), "{} is bound twice", key);
+        }
+    }
+
+    #[test]
+    fn parses_aspect_ratios() {
+        assert_eq!(parse_aspect("16:9"), Some(16. / 9.));
+        assert_eq!(parse_aspect("1:2"), Some(0.5));
+        assert_eq!(parse_aspect("2"), None);
+        assert_eq!(parse_aspect("1:0"), None);
+    }
+
+    #[test]
+    fn parses_tile_sizes() {
+        assert_eq!(parse_tile_size("32x16"), Some(Vec2::new(32., 16.)));
+        assert_eq!(parse_tile_size("8X8"), Some(Vec2::new(8., 8.)));
+        assert_eq!(parse_tile_size("32"), None);
+        assert_eq!(parse_tile_size("0x8"), None);
+    }
+
+    #[test]
+    fn dumped_presets_load_back_the_same_settings() {
+        let options = Options {
+            rows: 9,
+            padding: 2.5,
+            border: Some(6.),
+            atlas_width: Some(512.),
+            orientation: Orientation::Auto,
+            packer: Packer::Skyline,
+            sort: SortOrder::AreaDesc,
+            background: parse_hex_color("#10203040").unwrap(),
+            ..Options::default()
+        };
+        let path = std::env::temp_dir().join("texture_packer_preset_test.json");
+        std::fs::write(&path, options.preset_json()).unwrap();
+        let mut loaded = Options::default();
+        loaded.load_preset(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(loaded.preset_json(), options.preset_json());
+        assert_eq!(loaded.atlas_height, None);
+        assert_eq!(loaded.background, options.background);
+    }
+
+    #[test]
+    fn malformed_presets_name_the_bad_setting() {
+        let path = std::env::temp_dir().join("texture_packer_malformed_preset_test.json");
+        for (preset, expected) in [
+            ("{ \"rows\": 2.5 }", "\"rows\" should be a whole number"),
+            ("{ \"packer\": \"Tetris\" }", "unknown packer \"Tetris\""),
+            ("{ \"bg\": \"#ffffff\" }", "unknown setting \"bg\""),
+            ("[1, 2]", "expected an object of settings"),
+        ] {
+            std::fs::write(&path, preset).unwrap();
+            let error = Options::default().load_preset(&path).unwrap_err();
+            assert_eq!(&error, expected);
+        }
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn rejects_malformed_hex_colors() {
+        for hex in [
+            "",
+            "#",
+            "#fff",
+            "#ff800",
+            "#ff80000",
+            "#gg8000",
+            "#ff8000801",
+            "#+f8000",
+        ] {
+            assert!(parse_hex_color(hex).is_none(), "accepted {:?}", hex);
+        }
     }
 }